@@ -1,5 +1,14 @@
 pub mod weights;
 pub mod zobrist;
 pub mod options;
+pub mod opponent;
+pub mod params;
+pub mod personality;
+pub mod policy;
 pub mod clock;
+pub mod roots;
+pub mod skill;
+pub mod trace;
+pub mod endgame;
+pub mod solver;
 pub mod chara;
\ No newline at end of file
@@ -1,45 +1,60 @@
 // The main module of the chess engine.
 // ANY changes to the board MUST be done through the character's methods!
 
-use std::{cmp::{max, min, Ordering}, collections::HashSet, sync::mpsc::Receiver, thread, time::{Duration, Instant}};
+use std::{cmp::{max, min, Ordering}, collections::{HashSet, VecDeque}, fs, sync::{mpsc::Receiver, Arc}, thread, time::{Duration, Instant}};
 use rand::{rngs::ThreadRng, Rng};
 use crate::frame::{util::*, board::Board};
-use super::{clock::Clock, options::Options, weights::Weights, zobrist::Zobrist};
+use super::{clock::{Clock, TimeBudget}, endgame::{self, Endgames, Recognizer, SCALE_DRAW, SCALE_NORMAL}, options::Options, opponent::Opponent, params::Params, policy::Policy, roots::RootMoves, skill::Skill, solver::Solver, trace::{Term, Trace}, weights::{Weights, KING_DANGER_UNITS}, zobrist::Zobrist};
 
 /* CONSTANTS FOR STATIC EVALUATION */
 
 const CENTER: [u64; 2] = [0b0000000000000000000110000001100000011000000000000000000000000000, 0b0000000000000000000000000001100000011000000110000000000000000000];
+const SPACE:  [u64; 2] = [0x000000003C3C3C00, 0x003C3C3C00000000];   // c-f files on our side
+const HOME:   [u64; 2] = [0x0000000000000066, 0x6600000000000000];   // minor pieces start here
 const STRONG: [u64; 2] = [0b0000000001111110011111100011110000000000000000000000000000000000, 0b0000000000000000000000000000000000111100011111100111111000000000];
 
 const DEFAULT_VEC_CAPACITY: usize = 300;
+const HISTORY_MAX: i32 = 16384;                 // history scores are kept in [-HISTORY_MAX, HISTORY_MAX]
 
 enum GameResult {
     InProgress,
     WhiteWon,
-    Draw,
+    Draw(&'static str),                         // with the reason to claim it
     BlackWon
 }
 
 pub struct Chara {
     board:				Board,
     w:					Weights,
+    heatmap:            Arc<Heatmap>,           // a copy of w.heatmap the board sums incrementally
+    sp:                 Params,                 // search parameters
     baw:                i32,                    // aspiration window base
     
     /* Cache for evaluated positions as leafs (eval() result) or branches (search result with given a/b) */
     cache:		        Vec<EvalHash>,
+    pawn_cache:         Vec<PawnHash>,          // pawn structure terms by the pawn key
+    material_cache:     Vec<MaterialHash>,      // material imbalance by the material key
+    static_cache:       Vec<StaticHash>,        // static evals by the position hash
     
     /* Cache for already made in board moves to track drawish positions */
     history_vec:		Vec<u64>,				// previous board hashes stored here to call more quick hash_iter() function
     history_set:		HashSet<u64>,			// for fast checking if this position had occured before in this line
                                                 // note: it's always 1 hash behind
+    pawn_vec:           Vec<u64>,               // pawn keys, the same way as history_vec (null moves don't touch them)
     
     /* Accessible constants */
     zobrist:			Zobrist,
+    endgames:           Endgames,               // recognizers by material
     rng:				ThreadRng,
 
     /* Search trackers */
     ts:					Instant,				// timer start
     tl:					u128,					// time limit in ms
+    nl:                 u64,                    // node limit (strength limit only)
+    qs_limit:           usize,                  // no quiescence search beyond this ply (strength limit only)
+    qs_checks:          bool,                   // quiet checks in quiescence search
+    to:                 u128,                   // optimum time in ms (soft limit, scaled by search stability)
+    best_changes:       u32,                    // best move changes at root, halved every iteration
     abort:				bool,					// stop search signal
     nodes:				u64,					// nodes searched
     hmc:				usize,					// current distance to root of the search
@@ -49,9 +64,16 @@ pub struct Chara {
     tpv_len:			[usize; HALF_DEPTH_LIMIT],
                                                 // quiet moves that cause a beta cutoff
     killer:				[[u32; HALF_DEPTH_LIMIT]; 2],
+                                                // quiet moves that cause a beta cutoff, by [piece][square to]
+    history:            [[i32; 64]; 14],
+                                                // static eval of every node on the current line
+    sev:                [i32; HALF_DEPTH_LIMIT],
     tpv_flag:			bool,					// if this is a principle variation (in search)
     mate_flag:			bool,					// if mate is present
     cur_depth:          i16,                    // current depth of the iterative dfs (comm-related)
+    nmp_min_ply:        usize,                  // no null moves before this ply (set while verifying a null move cutoff)
+    contempt:           i32,                    // for the engine in score units (set by think())
+    draw_root:          i32,                    // draw score for the root side (engine or opfor if pondering)
 
     /* Static eval addon */
     castled:			[bool; 2],				// white used castle, black used castle
@@ -64,47 +86,74 @@ pub struct Chara {
     hard:               bool,                   // always pondering
     loop_force:         bool,                   // ignore command input in listen() for a current cycle
     playother:          bool,                   // send score for other side
-    draw_offered:       bool,                   // by engine itself
     draw_got_offer:     bool,                   // from opfor
-    resign_offered:     bool,                   // UNUSED by now
+    resign_offered:     bool,                   // game is over for us until new
+    policy:             Policy,                 // when to resign or offer/accept a draw
+    opponent:           Opponent,               // who we play against and where
+    skill:              Skill,                  // strength limit
+    solver:             Solver,                 // mate in N for chess problems
     quit:               bool,                   // received in update()
     post:               bool,                   // post non-debug calculations info or not
     ping:               i32,                    // received in update(), but must be done when listen()
+    pending:            VecDeque<String>,       // received while solving, but must be done in listen()
     // depth_limit:     i32,
     enqueued_move:      u32,                    // received in update(), but must be done in listen()
     enqueued_reverts:   u32,                    // take back how many moves (comm got from update())
     clock:              Clock,
     started_black:      bool,                   // hotfix for unusual move transformation (playother for think())
-    legals:             Vec<u32>                // hotfix for a bug preventing usermove while pondering
+    legals:             Vec<u32>,               // hotfix for a bug preventing usermove while pondering
+    roots:              RootMoves               // root moves of the last think() with their scores and lines
 }
 
 impl Chara {
     pub fn init(fen: &str, rx: Receiver<String>) -> Self {
-        let board = Board::import(fen);
+        let w = Weights::init();
+        let heatmap = Arc::new(w.heatmap);
+        let mut board = Board::import(fen);
+        board.set_heatmap(Some(heatmap.clone()));
         let zobrist = Zobrist::default();
         let mut cache_perm_vec = Vec::with_capacity(DEFAULT_VEC_CAPACITY);
         cache_perm_vec.push(zobrist.cache_new(&board));
+        let mut pawn_vec = Vec::with_capacity(DEFAULT_VEC_CAPACITY);
+        pawn_vec.push(zobrist.pawn_new(&board));
 
         Self {
             board,
-            w:				    Weights::init(),
+            w,
+            heatmap,
+            sp:                 Params::default(),
             baw:                300, // pretty much default value, divide by 400 to get centipawns
             cache:	            vec![EvalHash::default(); 1 << CACHE_SIZE],
+            pawn_cache:         vec![PawnHash::default(); 1 << PAWN_CACHE_SIZE],
+            material_cache:     vec![MaterialHash::default(); 1 << MATERIAL_CACHE_SIZE],
+            static_cache:       vec![StaticHash::default(); 1 << STATIC_CACHE_SIZE],
             history_vec:	    cache_perm_vec,
             history_set:	    HashSet::default(),
+            pawn_vec,
             zobrist,
+            endgames:           Endgames::default(),
             rng:			    rand::thread_rng(),
             ts:				    Instant::now(),
             tl:				    0,
+            nl:                 u64::MAX,
+            qs_limit:           HALF_DEPTH_LIMIT,
+            qs_checks:          true,
+            to:                 0,
+            best_changes:       0,
             abort:			    false,
             nodes:			    0,
             hmc:			    0,
             tpv:			    [[0; HALF_DEPTH_LIMIT]; HALF_DEPTH_LIMIT],
             tpv_len:		    [0; HALF_DEPTH_LIMIT],
             killer:			    [[0; HALF_DEPTH_LIMIT]; 2],
+            history:            [[0; 64]; 14],
+            sev:                [0; HALF_DEPTH_LIMIT],
             tpv_flag:		    false,
             mate_flag:		    false,
             cur_depth:          0,
+            nmp_min_ply:        0,
+            contempt:           0,
+            draw_root:          0,
             castled:		    [false, false],
             rx,
             options:            Options::default(),
@@ -113,17 +162,22 @@ impl Chara {
             hard:               true,
             loop_force:         false,
             playother:          false,
-            draw_offered:       false,
             draw_got_offer:     false,
             resign_offered:     false,
+            policy:             Policy::default(),
+            opponent:           Opponent::default(),
+            skill:              Skill::default(),
+            solver:             Solver::default(),
             quit:               false,
             post:               false,
             ping:               i32::MIN,
+            pending:            VecDeque::new(),
             enqueued_move:      0,
             enqueued_reverts:   0,
             clock:              Clock::default(),
             started_black:      false,
-            legals:             Vec::default()
+            legals:             Vec::default(),
+            roots:              RootMoves::default()
         }
     }
 
@@ -135,7 +189,10 @@ impl Chara {
             thread::sleep(Duration::from_millis(1));
             
             if !self.loop_force {
-                let last = self.rx.try_recv();
+                let last = match self.pending.pop_front() {
+                    Some(line) => Ok(line),
+                    None => self.rx.try_recv()
+                };
                 if last.is_err() {
                     continue;
                 }
@@ -150,17 +207,66 @@ impl Chara {
                         self.playother = self.board.turn;
                         self.force = false;
                     },
+                    "computer" | "ics" | "name" | "rating" => {
+                        self.opponent.parse(&line);
+                        self.policy.ambition = self.opponent.ambition();
+                    },
                     "easy" => {
                         self.hard = false;
                     },
+                    "eval" => {
+                        let trace = self.trace();
+                        trace.print();
+                    },
+                    "mate" => {
+                        // mate N - forced mates of the side to move in N moves or less, all keys and the proof there's no shorter one
+                        match cmd.get(1).and_then(|n| n.parse::<usize>().ok()) {
+                            Some(limit) if limit > 0 => {
+                                let mut board = self.board.clone();
+                                let rx = &self.rx;
+                                let pending = &mut self.pending;
+                                // "?" is only a stop, everything else is done after the report
+                                let report = self.solver.solve(&mut board, limit, &mut || match rx.try_recv() {
+                                    Ok(line) if line.trim() == "?" => true,
+                                    Ok(line) => {
+                                        let stop = matches!(line.trim(), "quit" | "force" | "new");
+                                        pending.push_back(line);
+                                        stop
+                                    },
+                                    Err(_) => false
+                                });
+                                report.print(limit, self.board.turn, self.solver.checks_only);
+                            },
+                            _ => println!("Error (bad mate limit): {}", line.trim())
+                        }
+                    },
+                    "symmetry" => {
+                        // symmetry [FILE] - FENs or EPDs one per line, the current position if there's no file
+                        let fens = match cmd.get(1) {
+                            Some(path) => match fs::read_to_string(path) {
+                                Ok(text) => text.lines().filter_map(epd_to_fen).collect::<Vec<String>>(),
+                                Err(error) => {
+                                    println!("Error (cannot read {}): {}", path, error);
+                                    Vec::new()
+                                }
+                            },
+                            None => vec![self.board.export()]
+                        };
+                        let mut count = 0;
+                        for fen in fens.iter() {
+                            if let Some(report) = self.asymmetry(&Board::import(fen)) {
+                                println!("#DEBUG\tAsymmetric: {}", report);
+                                count += 1;
+                            }
+                        }
+                        println!("#DEBUG\tSymmetry: {} of {} positions are asymmetric", count, fens.len());
+                    },
                     "draw" => {
-                        let temp = self.playother;
-                        self.playother = self.hard;
-                        if self.considerate_draw(0) {
+                        // if pondering, the last score is from opfor side
+                        if self.accept_draw(self.hard) {
                             self.post();
                             println!("offer draw");
                         }
-                        self.playother = temp;
                     },
                     "force" | "result" => {
                         self.force = true;
@@ -168,9 +274,9 @@ impl Chara {
                     "go" => {
                         self.playother = false;
                         self.force = false;
-                        let ctime = self.time_alloc();
-                        println!("#DEBUG\tTime limit: {} ms", ctime);
-                        let em = self.think(self.baw, ctime, HALF_DEPTH_LIMIT_SAFE);
+                        let budget = self.time_alloc();
+                        println!("#DEBUG\tTime limit: {} ms (optimum {} ms)", budget.maximum, budget.optimum);
+                        let em = self.think(self.baw, budget, HALF_DEPTH_LIMIT_SAFE);
                         if !self.force {
                             self.enqueued_move = em.mov;
                         }
@@ -179,15 +285,16 @@ impl Chara {
                         self.hard = true;
                     },
                     "level" => {
+                        // extra triples are the next periods of a multi-period control
                         match cmd.len().cmp(&4) {
-                            Ordering::Equal => {
-                                self.clock.level(cmd[1], cmd[2], cmd[3]);
-                            },
                             Ordering::Less => {
                                 println!("Error (too few parameters): {}", line.trim());
                             },
-                            Ordering::Greater => {
+                            _ if (cmd.len() - 1) % 3 != 0 => {
                                 println!("Error (too many parameters): {}", line.trim());
+                            },
+                            _ => {
+                                self.clock.level(&cmd[1..]);
                             }
                         }
                     },
@@ -199,10 +306,10 @@ impl Chara {
                         self.post = false;
                     },
                     "option" => {
-                        if cmd.is_empty() {
+                        if cmd.len() < 2 {
                             println!("Error (too few parameters): {}", line.trim());
                         } else {
-                            self.options.parse(cmd[1]);
+                            self.set_option(cmd[1]);
                         }
                     },
                     "otim" => {
@@ -214,7 +321,7 @@ impl Chara {
                     "playother" => {
                         self.playother = true;
                         self.force = false;
-                        let _ = self.think(self.baw, PONDER_TIME, HALF_DEPTH_LIMIT_SAFE);
+                        let _ = self.think(self.baw, TimeBudget::unlimited(), HALF_DEPTH_LIMIT_SAFE);
                     },
                     "post" => {
                         self.post = true;
@@ -223,8 +330,19 @@ impl Chara {
                         if cmd[1] == "2" {
                             println!("feature done=0");
                             println!("feature myname=\"{}\"", MYNAME);
-                            println!("feature analyze=0 debug=1 ping=1 setboard=1 usermove=1");
+                            println!("feature analyze=0 debug=1 ics=1 name=1 ping=1 setboard=1 usermove=1");
                             println!("feature option=\"Random -spin 5 0 50\"");
+                            println!("feature option=\"MultiPV -spin 1 1 64\"");
+                            println!("feature option=\"MoveOverhead -spin 50 0 5000\"");
+                            println!("feature option=\"Contempt -spin 10 -200 200\"");
+                            println!("feature option=\"DynamicContempt -check 0\"");
+                            println!("feature option=\"Personality -combo {}\"", self.options.personality.combo());
+                            println!("feature option=\"WeightsFile -file {}\"", self.options.weights_file);
+                            self.clock.features();
+                            self.policy.features();
+                            self.skill.features();
+                            self.solver.features();
+                            self.sp.features();
                             println!("feature done=1");
                         } else {
                             // insert crashcode LOL
@@ -317,29 +435,39 @@ impl Chara {
                 if self.enqueued_move != 1 {
                     if self.draw_got_offer {
                         self.draw_got_offer = false;
-                        if self.considerate_draw(0) {
+                        if self.accept_draw(self.playother) {
                             self.post();
                             println!("offer draw");
                         }
                     }
-                    println!("#Debug\tMaking move {}...", self.enqueued_move);
-                    self.make_move(self.enqueued_move);
-                    if !(self.force || self.playother) {
-                        self.post();
-                        println!("move {}", move_transform(self.enqueued_move, !self.board.turn));
-                        if !self.draw_offered && self.considerate_draw(0) {
-                            println!("offer draw");
-                            self.draw_offered = true;
+                    let ours = !(self.force || self.playother);
+                    if ours {
+                        self.policy.on_move(self.last_score);
+                    }
+                    if ours && self.policy.should_resign() {
+                        println!("resign");
+                        self.opponent.log();
+                        self.resign_offered = true;
+                        self.force = true;
+                    } else {
+                        println!("#Debug\tMaking move {}...", self.enqueued_move);
+                        self.make_move(self.enqueued_move);
+                        if ours {
+                            self.post();
+                            println!("move {}", move_transform(self.enqueued_move, !self.board.turn));
+                            if self.policy.should_offer_draw(self.last_score, self.draw_value(), self.clock.is_it_time_for_draw(), self.board.no / 2) {
+                                println!("offer draw");
+                            }
                         }
                     }
                 }
                 self.enqueued_move = 0;
                 if !self.force {
-                    let mut ctime = PONDER_TIME;
+                    let mut budget = TimeBudget::unlimited();
                     self.playother = !self.playother;
                     if !self.playother {
-                        ctime = self.time_alloc();
-                        println!("#DEBUG\tTime limit: {} ms", ctime);
+                        budget = self.time_alloc();
+                        println!("#DEBUG\tTime limit: {} ms (optimum {} ms)", budget.maximum, budget.optimum);
                     }
                     match self.get_result() {
                         GameResult::InProgress => {
@@ -347,7 +475,7 @@ impl Chara {
                                 continue;
                             }
                             
-                            let em = self.think(self.baw, ctime, HALF_DEPTH_LIMIT_SAFE);
+                            let em = self.think(self.baw, budget, HALF_DEPTH_LIMIT_SAFE);
                             if !self.playother {
                                 self.enqueued_move = em.mov;
                             }
@@ -358,13 +486,15 @@ impl Chara {
                         GameResult::WhiteWon => {
                             println!("result 1-0 checkmate");
                         },
-                        GameResult::Draw => {
-                            println!("result 1/2-1/2");
+                        GameResult::Draw(reason) => {
+                            println!("result 1/2-1/2 {{{}}}", reason);
                         },
                         GameResult::BlackWon => {
                             println!("result 0-1 checkmate");
                         },
                     }
+                    // game is over, so it's on the record who it was against
+                    self.opponent.log();
                 }
             }
             
@@ -382,11 +512,13 @@ impl Chara {
         }
 
         if self.nodes & NODES_BETWEEN_COMMS_PASSIVE == 0 || (self.playother && self.nodes & NODES_BETWEEN_COMMS_ACTIVE == 0) {
-            let last = self.rx.try_recv();
-            if last.is_ok() {
-                let line = last.unwrap();
+            if let Ok(line) = self.rx.try_recv() {
                 let cmd = line.trim().split(' ').collect::<Vec<&str>>();
                 match cmd[0] {
+                    "computer" | "ics" | "name" | "rating" => {
+                        self.opponent.parse(&line);
+                        self.policy.ambition = self.opponent.ambition();
+                    },
                     "?" => {
                         self.abort = true;
                     },
@@ -401,10 +533,10 @@ impl Chara {
                         self.post = false;
                     },
                     "option" => {
-                        if cmd.is_empty() {
+                        if cmd.len() < 2 {
                             println!("Error (too few parameters): {}", line.trim());
                         } else {
-                            self.options.parse(cmd[1]);
+                            self.set_option(cmd[1]);
                         }
                     },
                     "otim" => {
@@ -451,7 +583,7 @@ impl Chara {
                             }
                         }
                     },
-                    "accepted" | "black" | "easy" | "go" | "hard" | "level" | "new" | "playother" | "protover" | "rejected" | "setboard" | "st" | "xboard" | "white" => {
+                    "accepted" | "black" | "easy" | "go" | "hard" | "level" | "mate" | "new" | "playother" | "protover" | "rejected" | "setboard" | "st" | "xboard" | "white" => {
                         println!("Error (command not legal now): {}", cmd[0]);
                     },
                     _ => {
@@ -468,107 +600,192 @@ impl Chara {
                 }
             }
         
-            if (self.nodes & NODES_BETWEEN_POSTS == 0) && self.tpv_len[0] != 0 {
+            if self.nodes & NODES_BETWEEN_POSTS == 0 {
                 self.post();
             }
         }
     }
 
-    fn think(&mut self, base_aspiration_window: i32, time_limit_ms: u128, depth_limit: i16) -> EvalMove {
+    fn think(&mut self, base_aspiration_window: i32, budget: TimeBudget, depth_limit: i16) -> EvalMove {
         self.ts = Instant::now();
-        self.tl = time_limit_ms;
+        self.tl = budget.maximum;
+        self.to = budget.optimum;
+        self.best_changes = 0;
+        self.contempt = self.calc_contempt();
+        self.draw_root = if self.playother {
+            self.contempt
+        } else {
+            -self.contempt
+        };
         self.abort = false;
         self.mate_flag = false;
         self.nodes = 0;
+        self.nmp_min_ply = 0;
         for line in self.tpv.iter_mut() { for node in line.iter_mut() { *node = 0 } };
         for len in self.tpv_len.iter_mut() { *len = 0 };
         for num in self.killer.iter_mut() { for mov in num.iter_mut() { *mov = 0 } };
-        let mut alpha = -INF;
-        let mut beta  =  INF;
-        self.cur_depth = 1;
-        let mut k = 1;
+        for piece in self.history.iter_mut() { for score in piece.iter_mut() { *score /= 2 } };
         let mut score = 0;
+        self.cur_depth = 1;
         self.started_black = self.board.turn;
         self.legals = self.board.get_legal_moves();
-        loop {
-            self.tpv_flag = true;
-            let temp = self.search(alpha, beta, self.cur_depth);
-            if !self.abort {
-                score = temp;	
+        self.roots = RootMoves::new(&self.legals);
+        // limited strength: less depth and nodes, and a few lines to choose a (maybe) worse move from
+        let mut depth_limit = depth_limit;
+        let mut lines = self.options.multipv;
+        if self.skill.limit {
+            depth_limit = min(depth_limit, self.skill.depth());
+            lines = max(lines, self.skill.lines());
+            self.nl = self.skill.nodes();
+            self.qs_checks = self.skill.qs_checks();
+        } else {
+            self.nl = u64::MAX;
+            self.qs_checks = true;
+        }
+        let lines = min(lines, self.roots.len());
+        let mut mate_done = false;
+        let mut last_best = 0;
+        let mut last_score = 0;
+        let mut stable = 0;
+        'deepening: while !self.roots.is_empty() {
+            self.roots.begin_iteration();
+            self.qs_limit = if self.skill.limit {
+                self.cur_depth as usize + self.skill.qs_plies()
             } else {
-                println!("#DEBUG\tAbort signal reached!");
-                break;
-            }
-            self.last_score = score_to_gui(score, false);
-            if self.tpv_len[0] != 0 {
-                self.post();
-            }
-            if !(-LARGM..=LARGM).contains(&score) {
-                if self.mate_flag {
+                HALF_DEPTH_LIMIT
+            };
+            // every line gets its own aspiration window around its previous score
+            for pv_idx in 0..lines {
+                self.roots.pick_line(pv_idx);
+                let prev = self.roots.moves[pv_idx].prev_score;
+                let (mut alpha, mut beta) = if self.cur_depth > 1 && (-LARGM..=LARGM).contains(&prev) {
+                    (prev - base_aspiration_window, prev + base_aspiration_window)
+                } else {
+                    (-INF, INF)
+                };
+                let mut k = 1;
+                loop {
+                    let temp = self.search_root(alpha, beta, self.cur_depth, pv_idx);
+                    if self.abort {
+                        println!("#DEBUG\tAbort signal reached!");
+                        break 'deepening;
+                    }
+                    self.roots.sort_lines(pv_idx);
+                    self.post_line(pv_idx);
+                    if pv_idx == 0 {
+                        score = temp;
+                        self.last_score = score_to_gui(score, false);
+                        if !(-LARGM..=LARGM).contains(&score) {
+                            if self.mate_flag {
+                                mate_done = true;
+                                break;
+                            }
+                            println!("#DEBUG\tMate detected.");
+                            alpha = -INF;
+                            beta = INF;
+                            self.mate_flag = true;
+                            continue;
+                        }
+                    }
+                    if temp <= alpha || temp >= beta {
+                        if k > 15 {
+                            alpha = -INF;
+                            beta = INF;
+                            println!("#DEBUG\tAlpha/beta fail! Using INFINITE values now.");
+                            continue;
+                        }
+                        k *= 2;
+                        alpha = alpha + base_aspiration_window * k - base_aspiration_window * (k * 2);
+                        beta = beta - base_aspiration_window * k + base_aspiration_window * (k * 2);
+                        println!("#DEBUG\tAlpha/beta fail! Using x{} from base aspiration now.", k);
+                        continue;
+                    }
                     break;
                 }
-                println!("#DEBUG\tMate detected.");
-                alpha = -INF;
-                beta = INF;
-                self.mate_flag = true;
-                continue;
-            }
-            if score <= alpha || score >= beta {
-                if k > 15 {
-                    alpha = -INF;
-                    beta = INF;
-                    println!("#DEBUG\tAlpha/beta fail! Using INFINITE values now.");
-                    continue;
-                }
-                k *= 2;
-                alpha = alpha + base_aspiration_window * k - base_aspiration_window * (k * 2);
-                beta = beta - base_aspiration_window * k + base_aspiration_window * (k * 2);
-                println!("#DEBUG\tAlpha/beta fail! Using x{} from base aspiration now.", k);
-                continue;
             }
+            // moves that took more effort to refute are more likely to become the best ones later
+            self.roots.reorder(lines);
 
-            self.last_score = score_to_gui(score, false);
-            alpha = score - base_aspiration_window;
-            beta = score + base_aspiration_window;
-            k = 1;
+            if mate_done {
+                break;
+            }
             self.cur_depth += 1;
             if self.cur_depth > depth_limit || self.ts.elapsed().as_millis() > self.tl {
                 break;
             }
+
+            // soft limit: less time if the best move holds, more if it keeps changing or the score falls
+            if self.to != PONDER_TIME {
+                if self.roots.len() == 1 {
+                    break;
+                }
+                let best = self.roots.moves[0].mov;
+                stable = if best == last_best { stable + 1 } else { 0 };
+                let stability = 130 - 10 * min(stable, 6) as u128;
+                let changes = 100 + min(30 * self.best_changes as u128, 100);
+                let falling = if self.cur_depth > 2 { (100 + (last_score - score) / 8).clamp(100, 150) as u128 } else { 100 };
+                let soft = self.to * stability * changes / 10000 * falling / 100;
+                if self.ts.elapsed().as_millis() > min(soft, self.tl) {
+                    break;
+                }
+                last_best = best;
+                last_score = score;
+                self.best_changes /= 2;
+            }
         }
 
         let approx = self.ts.elapsed().as_millis() + 1;
         self.clock.time_deduct(&approx, self.playother);
         println!("#DEBUG\tApproximate time spent: {} ms", approx);
-        EvalMove::new(self.tpv[0][0], score)
+        if self.skill.limit && !self.roots.is_empty() {
+            let scores = self.roots.moves.iter().take(lines).filter(|rm| rm.score != -INF).map(|rm| score_to_gui(rm.score, false)).collect::<Vec<i32>>();
+            let pick = self.skill.pick(&scores, self.rng.gen::<f64>());
+            println!("#DEBUG\tLimited strength: line {} out of {} is picked", pick + 1, scores.len());
+            let rm = &self.roots.moves[pick];
+            return EvalMove::new(rm.mov, rm.score);
+        }
+        match self.roots.moves.first() {
+            Some(rm) => EvalMove::new(rm.mov, score),
+            None => EvalMove::new(0, 0)
+        }
     }
 
     fn clear(&mut self) {
         self.board = Board::default();
+        self.board.set_heatmap(Some(self.heatmap.clone()));
         self.history_set.clear();
         self.history_vec = Vec::with_capacity(DEFAULT_VEC_CAPACITY);
         self.history_vec.push(self.zobrist.cache_new(&self.board));
+        self.pawn_vec = Vec::with_capacity(DEFAULT_VEC_CAPACITY);
+        self.pawn_vec.push(self.zobrist.pawn_new(&self.board));
         self.castled = [false, false];
         self.cache.clear();
         self.cache.resize(1 << CACHE_SIZE, EvalHash::default());
+        self.clear_eval_caches();
         self.cur_depth = 0;
-        self.draw_offered = false;
+        self.history = [[0; 64]; 14];
         self.draw_got_offer = false;
         self.resign_offered = false;
+        self.policy.reset();
+        self.opponent.reset();
+        self.policy.ambition = 0;
         self.playother = false;
         self.force = false;
         self.nodes = 0;
         self.enqueued_move = 0;
         self.enqueued_reverts = 0;
         self.last_score = 0;
-        self.clock = Clock::default();
+        self.clock.reset();
     }
 
     fn set_pos(&mut self, fen: &str) {
         self.clear();
         self.board = Board::import(fen);
+        self.board.set_heatmap(Some(self.heatmap.clone()));
         self.history_vec.pop();
         self.history_vec.push(self.zobrist.cache_new(&self.board));
+        self.pawn_vec.pop();
+        self.pawn_vec.push(self.zobrist.pawn_new(&self.board));
     }
 
     fn make_move(&mut self, mov: u32) {
@@ -580,6 +797,8 @@ impl Chara {
         self.board.make_move(mov);
         let hash = self.zobrist.cache_iter(&self.board, mov, prev_hash);
         self.history_vec.push(hash);
+        let pawn_hash = self.zobrist.pawn_iter(&self.board, mov, *self.pawn_vec.last().unwrap());
+        self.pawn_vec.push(pawn_hash);
     }
 
     fn revert_move(&mut self) {
@@ -589,6 +808,74 @@ impl Chara {
         self.board.revert_move();
         self.history_vec.pop();
         self.history_set.remove(self.history_vec.last().unwrap());
+        self.pawn_vec.pop();
+    }
+
+    fn make_null_move(&mut self) {
+        let prev_hash = *self.history_vec.last().unwrap();
+        self.history_set.insert(prev_hash);
+        self.board.make_null_move();
+        let hash = self.zobrist.cache_null(&self.board, prev_hash);
+        self.history_vec.push(hash);
+    }
+
+    fn unmake_null_move(&mut self) {
+        self.board.unmake_null_move();
+        self.history_vec.pop();
+        self.history_set.remove(self.history_vec.last().unwrap());
+    }
+
+    // root node: moves are taken from the root move list and their results are kept there
+    // moves before pv_idx are the better lines of this iteration already, so they are skipped
+    fn search_root(&mut self, mut alpha: i32, beta: i32, mut depth: i16, pv_idx: usize) -> i32 {
+        self.nodes += 1;
+        depth += self.board.is_in_check() as i16;
+        for i in pv_idx..self.roots.len() {
+            let mov = self.roots.moves[i].mov;
+            for node in self.tpv[0].iter_mut() { *node = 0 };
+            for (node, pv_mov) in self.tpv[0].iter_mut().zip(self.roots.moves[i].pv.iter()) {
+                *node = *pv_mov;
+            }
+            self.tpv_flag = i == pv_idx;
+
+            let nodes = self.nodes;
+            self.make_move(mov);
+            self.hmc += 1;
+            let mut score;
+            if i == pv_idx {
+                score = -self.search(-beta, -alpha, depth - 1);
+            } else {
+                score = -self.search(-alpha - 1, -alpha, depth - 1);
+                if score > alpha && score < beta {
+                    score = -self.search(-beta, -alpha, depth - 1);
+                }
+            }
+            self.hmc -= 1;
+            self.revert_move();
+            if self.abort {
+                return 0;
+            }
+
+            let rm = &mut self.roots.moves[i];
+            rm.nodes += self.nodes - nodes;
+            if score > alpha {
+                alpha = score;
+                rm.score = score;
+                rm.depth = self.cur_depth;
+                rm.pv.truncate(1);
+                rm.pv.extend_from_slice(&self.tpv[1][1..self.tpv_len[1]]);
+                if pv_idx == 0 && i != 0 {
+                    self.best_changes += 1;
+                }
+                self.roots.promote(pv_idx, i);
+                if alpha >= beta {
+                    return beta; // fail high
+                }
+            } else {
+                rm.score = -INF;
+            }
+        }
+        alpha
     }
 
     fn search(&mut self, mut alpha: i32, beta: i32, mut depth: i16) -> i32 {
@@ -597,7 +884,7 @@ impl Chara {
         let hash = *self.history_vec.last().unwrap();
         let hash_index = (hash & TEMP_PRE_CALC_CACHE_BITMASK) as usize;
         if self.hmc != 0 && (self.board.hmc > 99 || self.history_set.contains(&hash)) {
-            return self.w.rand + 1;
+            return self.draw_score();
         }
         
         let hash_is_same = self.cache[hash_index].hash == hash;
@@ -626,6 +913,10 @@ impl Chara {
         if self.nodes & NODES_BETWEEN_UPDATES == 0 {
             self.update();
         }
+        if self.nodes >= self.nl {
+            self.abort = true;
+            return 0;
+        }
         if depth <= 0 {
             return self.extension(alpha, beta);
         }
@@ -635,29 +926,48 @@ impl Chara {
         }
 
         let in_check = self.board.is_in_check();
+        let static_eval = if in_check {
+            -INF
+        } else {
+            self.eval()
+        };
+        self.sev[self.hmc] = static_eval;
+        let improving = self.hmc < 2 || self.sev[self.hmc - 2] == -INF || static_eval > self.sev[self.hmc - 2];
+        let pv_node = beta - alpha > 1;
 
         // Null move prune
-        if !in_check && self.hmc != 0 && depth > 2 {
+        // - not in pv nodes, not twice in a row and not while verifying a previous one
+        // - not without pieces, because pawn endings are full of zugzwangs
+        // - reduction grows with depth and with how far static eval is above beta
+        if !in_check && self.hmc != 0 && !pv_node && static_eval >= beta && depth >= self.sp.nmp_min_depth && self.hmc >= self.nmp_min_ply
+            && beta.abs() < LARGM && self.board.move_history.last() != Some(&0) && self.board.has_non_pawn_material(self.board.turn) {
+            let reduction = self.sp.nmp_base + depth / self.sp.nmp_depth_div + min(((static_eval - beta) / self.sp.nmp_eval_div) as i16, self.sp.nmp_eval_max);
+
+            self.make_null_move();
             self.hmc += 1;
-            self.board.turn = !self.board.turn;
-            self.history_set.insert(*self.history_vec.last().unwrap());
-            self.history_vec.push(*self.history_vec.last().unwrap() ^ self.zobrist.hash_turn ^ self.zobrist.hash_en_passant[self.board.en_passant]);
-            let old_en_passant = self.board.en_passant;
-            self.board.en_passant = 0;
-
-            let score = -self.search(-beta, -beta + 1, depth - 3);	// reduction = 2
-
-            self.board.turn = !self.board.turn;
-            self.board.en_passant = old_en_passant;
-            self.history_vec.pop();
-            self.history_set.remove(self.history_vec.last().unwrap());
+            let score = -self.search(-beta, -beta + 1, depth - reduction - 1);
             self.hmc -= 1;
+            self.unmake_null_move();
 
             if self.abort {
                 return 0;
             }
             if score >= beta {
-                return beta;
+                if depth < self.sp.nmp_verify_depth || self.nmp_min_ply != 0 {
+                    return beta;
+                }
+
+                // verification search: same node without a null move, but reduced (and no null moves for a while)
+                self.nmp_min_ply = self.hmc + (3 * (depth - reduction) / 4) as usize;
+                let score = self.search(beta - 1, beta, depth - reduction);
+                self.nmp_min_ply = 0;
+
+                if self.abort {
+                    return 0;
+                }
+                if score >= beta {
+                    return beta;
+                }
             }
         }
 
@@ -712,20 +1022,37 @@ impl Chara {
         moves.reverse();
         
         let mut hf_cur = HF_LOW;
+        let mut quiets = Vec::with_capacity(moves.len());
         depth += in_check as i16;
         // a/b with lmr and pv proving
         for (i, mov) in moves.iter().enumerate() {
+            let quiet = *mov & MFE_CLEAR < ME_CAPTURE_MIN && move_get_promotion(*mov) == E;
+            let piece = move_get_piece(*mov);
+            let to = move_get_to(*mov, self.board.turn);
             self.make_move(*mov);
+            let gives_check = self.board.is_in_check();
             self.hmc += 1;
-            let mut score = if i != 0 && depth > 2 && !(*mov > ME_PROMISING_MIN || in_check) {
-                -self.search(-beta, -alpha, depth - 2 - (depth > 3 && i > 7 && i + 9 > moves.len()) as i16)
+            let mut score;
+            if i == 0 {
+                score = -self.search(-beta, -alpha, depth - 1);
             } else {
-                alpha + 1
-            };
-            if score > alpha {
-                score = -self.search(-alpha - 1, -alpha, depth - 1);
+                // late quiet moves are reduced by the table, less so if they look any good
+                let mut reduction = 0;
+                if quiet && !in_check && depth >= self.sp.lmr_min_depth && i >= self.sp.lmr_min_moves {
+                    reduction = self.sp.lmr_table[min(depth, 63) as usize][min(i, 63)];
+                    reduction -= pv_node as i16;
+                    reduction += !improving as i16;
+                    reduction -= (*mov & (MFE_KILLER1 | MFE_KILLER2) != 0) as i16;
+                    reduction -= gives_check as i16;
+                    reduction -= (self.history[piece][to] / self.sp.lmr_history_div) as i16;
+                    reduction = reduction.clamp(0, max(depth - 2, 0));
+                }
+                score = -self.search(-alpha - 1, -alpha, depth - 1 - reduction);
+                if score > alpha && reduction != 0 {
+                    score = -self.search(-alpha - 1, -alpha, depth - 1);
+                }
                 if score > alpha && score < beta {
-                    score = -self.search(-beta, -alpha, depth - 1)
+                    score = -self.search(-beta, -alpha, depth - 1);
                 }
             }
             self.hmc -= 1;
@@ -755,9 +1082,19 @@ impl Chara {
                         self.killer[1][self.hmc] = self.killer[0][self.hmc];
                         self.killer[0][self.hmc] = *mov & MFE_CLEAR;
                     }
+                    if quiet {
+                        let bonus = min(depth as i32 * depth as i32, HISTORY_MAX >> 2);
+                        self.update_history(piece, to, bonus);
+                        for (qpiece, qto) in quiets.into_iter() {
+                            self.update_history(qpiece, qto, -bonus);
+                        }
+                    }
                     return beta; // fail high
                 }
             }
+            if quiet {
+                quiets.push((piece, to));
+            }
         }
 
         if hash_is_same || depth > min(self.cache[hash_index].depth, 4) {
@@ -772,6 +1109,13 @@ impl Chara {
         alpha // fail low
     }
 
+    // keeps the score bounded, the closer it gets to the limit, the less it changes
+    #[inline]
+    fn update_history(&mut self, piece: usize, to: usize, bonus: i32) {
+        let score = &mut self.history[piece][to];
+        *score += bonus - *score * bonus.abs() / HISTORY_MAX;
+    }
+
     fn extension(&mut self, mut alpha: i32, beta: i32) -> i32 {
         if self.nodes & NODES_BETWEEN_UPDATES == 0 {
             self.update();
@@ -779,10 +1123,13 @@ impl Chara {
         self.nodes += 1;
 
         // cuttin even before we get a list of moves
-        alpha = max(alpha, self.eval());
+        alpha = max(alpha, self.eval_lazy(alpha, beta));
         if alpha >= beta {
             return beta; // fail high
         }
+        if self.hmc >= self.qs_limit {
+            return alpha;
+        }
 
         let mut moves = self.board.get_legal_moves();
         
@@ -800,7 +1147,7 @@ impl Chara {
         for mov in moves.iter() {
             self.make_move(*mov);
             // extension will consider checks as well as captures
-            if *mov < ME_CAPTURE_MIN && !self.board.is_in_check() {
+            if *mov < ME_CAPTURE_MIN && (!self.qs_checks || !self.board.is_in_check()) {
                 self.revert_move();
                 continue;
             }
@@ -825,14 +1172,61 @@ impl Chara {
         2) Search MUST determine if the game ended! Eval does NOT evaluate staled/mated positions specifically.
         3) Eval is not great on evaluating checks and detecting possibilities - it's HCE, wdy want?
     */
+    // with Random on, the noise is drawn on every call, so nothing is cached
     fn eval(&mut self) -> i32 {
+        if self.w.rand != 0 {
+            return self.eval_traced(None);
+        }
+        let hash = *self.history_vec.last().unwrap();
+        let index = (hash & STATIC_CACHE_BITMASK) as usize;
+        if self.static_cache[index].hash == hash {
+            return self.static_cache[index].score;
+        }
+        let score = self.eval_traced(None);
+        self.static_cache[index] = StaticHash { hash, score };
+        score
+    }
+
+    // same as eval(), unless material and squares alone are outside the window by more than the margin, then it's the bound
+    // endings are never cut this way: their scaling and recognizers may take all of that material away
+    fn eval_lazy(&mut self, alpha: i32, beta: i32) -> i32 {
+        let hash = *self.history_vec.last().unwrap();
+        let entry = self.static_cache[(hash & STATIC_CACHE_BITMASK) as usize];
+        if entry.hash == hash && self.w.rand == 0 {
+            return entry.score;
+        }
+        if self.sp.lazy_margin != 0 && self.board.phase >= 18 {
+            let rough = self.eval_rough();
+            if rough - self.sp.lazy_margin >= beta {
+                return rough - self.sp.lazy_margin;
+            }
+            if rough + self.sp.lazy_margin <= alpha {
+                return rough + self.sp.lazy_margin;
+            }
+        }
+        self.eval()
+    }
+
+    // material and squares the board keeps summed, tapered, from the side to move
+    #[inline]
+    fn eval_rough(&self) -> i32 {
+        let phase_diff = self.phase_diff();
+        let psqt = self.board.psqt;
+        let score = ((psqt[0][0] + psqt[1][0]) as f32 * phase_diff + (psqt[0][1] + psqt[1][1]) as f32 * (1.0 - phase_diff)) as i32;
+        if self.board.turn { -score } else { score }
+    }
+
+    // weight of the opening part, [18 - 56] phase range
+    #[inline]
+    fn phase_diff(&self) -> f32 {
+        f32::min((max(18, self.board.phase) - 18) as f32 * 0.0264, 1.0)
+    }
+
+    // same as eval(), but every term is also added to the trace if there's one (for the eval command)
+    fn eval_traced(&mut self, mut trace: Option<&mut Trace>) -> i32 {
         /* SETUP SCORE APPLICATION */
 
-        let counter = (self.board.bbs[N] | self.board.bbs[N2]).count_ones() * 3 + 
-                      (self.board.bbs[B] | self.board.bbs[B2]).count_ones() * 3 + 
-                      (self.board.bbs[R] | self.board.bbs[R2]).count_ones() * 4 +
-                      (self.board.bbs[Q] | self.board.bbs[Q2]).count_ones() * 8;
-        // 56 - full board, 30 - most likely, endgame?..
+        let counter = self.board.phase;
 
         if counter < 4 && self.board.bbs[P] | self.board.bbs[P2] == 0 {
             return 0;
@@ -840,15 +1234,50 @@ impl Chara {
 
         let mut score: i32 = 0;
         let mut score_pd: [i32; 2] = [0, 0];
-        // [18 - 56] range
-        let phase_diff = f32::min((max(18, counter) - 18) as f32 * 0.0264, 1.0);
+        // a term for the colour it's signed for, the same for both phases or [opening, endgame]
+        macro_rules! add {
+            ($term:ident, $colour:expr, $value:expr) => {{
+                let value = $value;
+                score += value;
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.add(Term::$term, $colour, value, value);
+                }
+            }};
+        }
+        macro_rules! add_pd {
+            ($term:ident, $colour:expr, $mg:expr, $eg:expr) => {{
+                let (mg, eg) = ($mg, $eg);
+                score_pd[0] += mg;
+                score_pd[1] += eg;
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace.add(Term::$term, $colour, mg, eg);
+                }
+            }};
+        }
+        let phase_diff = self.phase_diff();
+
+        // known endgames are evaluated on their own
+        let material_key = endgame::material_key(&self.board);
+        let recognizer = self.endgames.probe(material_key);
+        if let Some((Recognizer::Value(value), strong)) = recognizer {
+            let value = value(&self.board, strong);
+            add!(Endgame, strong, if strong == 0 { value } else { -value });
+            if let Some(trace) = trace {
+                trace.phase = phase_diff;
+                trace.tapered = score;
+                trace.total = score;
+            }
+            return if self.board.turn { -score } else { score };
+        }
 
         let mut pattacks     = [0; 2];
-        let mut mobility     = [0; 2];
         let mut pins         = [0; 2];
         let mut sof			 = [0; 2];
         let mut ppt          = [0; 2];
         let mut pass         = [0; 2];
+        let mut attacked_by  = [[0; 5]; 2];        // [colour][p, n, b, r, q] attack maps (with defended allies)
+        let mut attackers    = [0; 2];             // pieces attacking the enemy king zone
+        let mut units        = [0; 2];             // king safety attack units against the enemy king
 
         // quality of life fr
         let bptr = &self.board.bbs;
@@ -857,6 +1286,7 @@ impl Chara {
         let sides = [self.board.get_occupancies(false), self.board.get_occupancies(true)];
         let occup = sides[0] | sides[1];
         let kbits = [gtz(bptr[K]), gtz(bptr[K2])];
+        let zone  = [mptr.attacks_king[kbits[0]] | bptr[K], mptr.attacks_king[kbits[1]] | bptr[K2]];
 
         let rpin = [bptr[N] | bptr[B] | bptr[Q], bptr[N2] | bptr[B2] | bptr[Q2]]; // if attack is on Q, it's profitable (most likely will be detected by extension)
         let bpin = [bptr[N] | bptr[R] | bptr[Q], bptr[N2] | bptr[R2] | bptr[Q2]]; // if attack is on R/Q, it's profitable
@@ -865,149 +1295,261 @@ impl Chara {
 
         /* SCORE APPLICATION BEGIN */
 
-        // pawn quick detections
-        for (ally, mut bb) in [bptr[P], bptr[P2]].into_iter().enumerate() {
-            let enemy = (ally == 0) as usize;
-            while bb != 0 {
-                let sq = pop_bit(&mut bb);
-                score_pd[0] += self.w.heatmap[0][P | ally][sq];
-                score_pd[1] += self.w.heatmap[1][P | ally][sq];
-                if bb & mptr.files[sq] != 0 {
-                    score += self.w.p_doubled[ally];
+        // pawn structure: terms that depend on pawns only come from the pawn cache along with their maps,
+        // the trace needs every term on its own, so it recomputes them (and doesn't store, the board may be a flipped one)
+        let pawn_hash  = *self.pawn_vec.last().unwrap();
+        let pawn_index = (pawn_hash & PAWN_CACHE_BITMASK) as usize;
+        let entry = self.pawn_cache[pawn_index];
+        let outpost_sqs;
+        if trace.is_none() && entry.hash == pawn_hash {
+            score += entry.score;
+            score_pd[0] += entry.score_pd[0];
+            score_pd[1] += entry.score_pd[1];
+            pattacks    = entry.pattacks;
+            pass        = entry.pass;
+            ppt         = entry.ppt;
+            sof         = entry.sof;
+            outpost_sqs = entry.outposts;
+        } else {
+            // pawn attacks are needed in advance for backward pawns
+            for (ally, mut bb) in [bptr[P], bptr[P2]].into_iter().enumerate() {
+                while bb != 0 {
+                    let sq = pop_bit(&mut bb);
+                    pattacks[ally] |= mptr.attacks_pawns[ally][sq];
                 }
-                if bptr[P | ally] & mptr.flanks[sq] & mptr.ranks[sq] != 0 {
-                    score += self.w.p_phalanga[ally];
-                } else {
-                    let mut flanks = 0;
-                    if sq & 7 != 0 {
-                        flanks += self.board.get_sliding_straight_opportunities(sq - 1, bptr[P] | bptr[P2]);
+            }
+
+            // pawn quick detections
+            for (ally, mut bb) in [bptr[P], bptr[P2]].into_iter().enumerate() {
+                let enemy = (ally == 0) as usize;
+                while bb != 0 {
+                    let sq = pop_bit(&mut bb);
+                    if bb & mptr.files[sq] != 0 {
+                        add!(Pawns, ally, self.w.p_doubled[ally]);
+                    }
+                    if bptr[P | ally] & mptr.flanks[sq] & mptr.ranks[sq] != 0 {
+                        add!(Pawns, ally, self.w.p_phalanga[ally]);
+                    } else {
+                        let mut flanks = 0;
+                        if sq & 7 != 0 {
+                            flanks += self.board.get_sliding_straight_opportunities(sq - 1, bptr[P] | bptr[P2]);
+                        }
+                        if sq & 7 != 7 {
+                            flanks += self.board.get_sliding_straight_opportunities(sq + 1, bptr[P] | bptr[P2]);
+                        }
+                        if flanks & mptr.flanks[sq] & bptr[P | ally] == 0 {
+                            add!(Pawns, ally, self.w.p_isolated[ally]);
+                        } else if mptr.flanks[sq] & !mptr.fwd[ally][sq] & bptr[P | ally] == 0 && mptr.step_pawns[ally][sq] & pattacks[enemy] != 0 {
+                            // neighbours are all ahead and it can't advance safely
+                            add!(Pawns, ally, self.w.p_backward[ally]);
+                        }
                     }
-                    if sq & 7 != 7 {
-                        flanks += self.board.get_sliding_straight_opportunities(sq + 1, bptr[P] | bptr[P2]);
+
+                    if (mptr.files[sq] | mptr.flanks[sq]) & mptr.fwd[ally][sq] & bptr[P | enemy] == 0 {
+                        add!(Passed, ally, self.w.p_passing[ally][sq >> 3]);
+                        pass[ally] |= 1 << sq;
+                        ppt[ally] |= mptr.files[sq] & mptr.fwd[ally][sq];
+                    } else if mptr.files[sq] & mptr.fwd[ally][sq] & bptr[P | enemy] == 0 {
+                        // only neighbouring pawns stand in the way, and there are enough of ours to deal with them
+                        let sentries = mptr.flanks[sq] & mptr.fwd[ally][sq] & bptr[P | enemy];
+                        let helpers  = mptr.flanks[sq] & !mptr.fwd[ally][sq] & bptr[P | ally];
+                        if helpers.count_ones() >= sentries.count_ones() {
+                            add!(Passed, ally, self.w.p_candidate[ally][sq >> 3]);
+                        }
                     }
-                    if flanks & mptr.flanks[sq] & bptr[P | ally] == 0 {
-                        score += self.w.p_isolated[ally];
+                    sof[ally] |= mptr.files[sq];
+                }
+            }
+            for ally in 0..2 {
+                let mut bb = pass[ally];
+                while bb != 0 {
+                    let sq = pop_bit(&mut bb);
+                    if get_bit(pattacks[ally], sq) != 0 {
+                        add!(Passed, ally, self.w.p_passer_protected[ally]);
+                    }
+                    if mptr.flanks[sq] & pass[ally] != 0 {
+                        add!(Passed, ally, self.w.p_passer_connected[ally]);
                     }
                 }
+            }
 
-                pattacks[ally] |= mptr.attacks_pawns[ally][sq];
-                if (mptr.files[sq] | mptr.flanks[sq]) & mptr.fwd[ally][sq] & bptr[P | enemy] == 0 {
-                    score += self.w.p_passing[ally][sq >> 3];
-                    pass[ally] |= 1 << sq;
-                    ppt[ally] |= mptr.files[sq] & mptr.fwd[ally][sq];
+            add!(Center, 0, (pattacks[0] & CENTER[0]).count_ones() as i32 * self.w.p_atk_center[0]);
+            add!(Center, 1, (pattacks[1] & CENTER[1]).count_ones() as i32 * self.w.p_atk_center[1]);
+            let mut outposts = [pattacks[0] & STRONG[0], pattacks[1] & STRONG[1]];
+            for (ally, mut bb) in outposts.into_iter().enumerate() {
+                let enemy = (ally == 0) as usize;
+                while bb != 0 {
+                    let sq = pop_bit(&mut bb);
+                    if mptr.flanks[sq] & mptr.fwd[ally][sq] & bptr[P | enemy] != 0 {
+                        del_bit(&mut outposts[ally], sq);
+                        continue;
+                    }
+                    add!(Outposts, ally, self.w.p_outpost[ally]);
+                    if mptr.step_pawns[ally][sq] & bptr[P | enemy] != 0 {
+                        add!(Outposts, enemy, self.w.p_outpost_block[enemy]);
+                    }
                 }
-                sof[ally] |= mptr.files[sq];
+            }
+            outpost_sqs = outposts;
 
-                let mut profit = mptr.attacks_pawns[ally][sq] & sides[enemy] & !bptr[P | enemy];
-                if profit != 0 {
-                    pop_bit(&mut profit);
-                    if profit != 0 {
-                        score += self.w.g_atk_pro[ally];
-                    } else {
-                        score += self.w.g_atk_pro_double[ally];
+            // nothing was added before, so the score is the pawn structure only
+            if trace.is_none() {
+                self.pawn_cache[pawn_index] = PawnHash { hash: pawn_hash, score, score_pd, pattacks, pass, ppt, sof, outposts };
+            }
+        }
+
+        // material imbalance depends on the material only
+        let material_index = (xor64(material_key) & MATERIAL_CACHE_BITMASK) as usize;
+        let imbalance = if self.material_cache[material_index].hash == material_key {
+            self.material_cache[material_index].score
+        } else {
+            let mut counts = [[0; 6]; 2];
+            for (ally, count) in counts.iter_mut().enumerate() {
+                count[0] = (bptr[B | ally].count_ones() > 1) as i32;
+                for (i, piece) in (P..K).step_by(2).enumerate() {
+                    count[i + 1] = bptr[piece | ally].count_ones() as i32;
+                }
+            }
+            let imbalance = self.w.imbalance(&counts);
+            self.material_cache[material_index] = MaterialHash { hash: material_key, score: imbalance };
+            imbalance
+        };
+        add!(Pieces, 0, imbalance[0]);
+        add!(Pieces, 1, imbalance[1]);
+
+        // mobility counts safe squares: not attacked by enemy pawns, not our king or our pawns that can't move
+        let blocked = [bptr[P] & (occup >> 8), bptr[P2] & (occup << 8)];
+        let area = [!(pattacks[1] | blocked[0] | bptr[K]), !(pattacks[0] | blocked[1] | bptr[K2])];
+
+        // space: safe squares in the center on our side, the ones behind our pawns count twice
+        for ally in 0..2 {
+            let enemy = (ally == 0) as usize;
+            let pawns = bptr[P | ally];
+            let behind = if ally == 0 { pawns >> 8 | pawns >> 16 | pawns >> 24 } else { pawns << 8 | pawns << 16 | pawns << 24 };
+            let safe = SPACE[ally] & !pawns & !pattacks[enemy];
+            let space = (safe.count_ones() + (safe & behind).count_ones()) as i32;
+            add_pd!(Center, ally, self.w.s_space[0][ally] * space, self.w.s_space[1][ally] * space);
+        }
+
+        // passed pawns: a free way, kings nearby and the rule of the square in pawn endgames
+        let mut unstoppable = [usize::MAX; 2];             // plies to promote the fastest unstoppable passer
+        for ally in 0..2 {
+            let enemy = (ally == 0) as usize;
+            let mut bb = pass[ally];
+            while bb != 0 {
+                let sq = pop_bit(&mut bb);
+                let rank = if ally == 0 { sq >> 3 } else { 7 - (sq >> 3) };
+                let stop = if ally == 0 { sq + 8 } else { sq - 8 };
+                let path = mptr.files[sq] & mptr.fwd[ally][sq];
+                if path & occup == 0 {
+                    add_pd!(Passed, ally, self.w.p_passer_free[0][ally] * rank as i32, self.w.p_passer_free[1][ally] * rank as i32);
+                }
+                let proximity = (min(distance(kbits[enemy], stop), 5) * 2) as i32 - min(distance(kbits[ally], stop), 5) as i32;
+                add_pd!(Passed, ally, self.w.k_passer_dist[0][ally] * proximity * rank as i32, self.w.k_passer_dist[1][ally] * proximity * rank as i32);
+
+                if path & occup == 0 && !self.board.has_non_pawn_material(enemy == 1) {
+                    let promotion = (sq & 7) | if ally == 0 { 56 } else { 0 };
+                    let moves = 7 - rank - (rank == 1) as usize;
+                    let enemy_to_move = self.board.turn == (enemy == 1);
+                    if distance(kbits[enemy], promotion) > moves + enemy_to_move as usize {
+                        unstoppable[ally] = min(unstoppable[ally], moves * 2 - !enemy_to_move as usize);
                     }
                 }
             }
         }
+        // in a race the one to promote first is the one who counts
+        for ally in 0..2 {
+            if unstoppable[ally] < unstoppable[(ally == 0) as usize] {
+                add_pd!(Passed, ally, self.w.p_unstoppable[0][ally], self.w.p_unstoppable[1][ally]);
+            }
+        }
+
         // 8 consequtive IFs for nails detection
         if get_bit(bptr[P], 10) != 0 && get_bit(sides[1], 18) != 0 {
-            score += self.w.p_semiblocked[0];
+            add!(Pawns, 0, self.w.p_semiblocked[0]);
         }
         if get_bit(bptr[P], 13) != 0 && get_bit(sides[1], 21) != 0 {
-            score += self.w.p_semiblocked[0];
+            add!(Pawns, 0, self.w.p_semiblocked[0]);
         }
         if get_bit(bptr[P], 11) != 0 && get_bit(occup, 19) != 0 {
-            score += self.w.p_blocked[0];
+            add!(Pawns, 0, self.w.p_blocked[0]);
         }
         if get_bit(bptr[P], 12) != 0 && get_bit(occup, 20) != 0 {
-            score += self.w.p_blocked[0];
+            add!(Pawns, 0, self.w.p_blocked[0]);
         }
         if get_bit(bptr[P2], 50) != 0 && get_bit(sides[0], 42) != 0 {
-            score += self.w.p_semiblocked[1];
+            add!(Pawns, 1, self.w.p_semiblocked[1]);
         }
         if get_bit(bptr[P2], 53) != 0 && get_bit(sides[0], 45) != 0 {
-            score += self.w.p_semiblocked[1];
+            add!(Pawns, 1, self.w.p_semiblocked[1]);
         }
         if get_bit(bptr[P2], 51) != 0 && get_bit(occup, 43) != 0 {
-            score += self.w.p_blocked[1];
+            add!(Pawns, 1, self.w.p_blocked[1]);
         }
         if get_bit(bptr[P2], 52) != 0 && get_bit(occup, 44) != 0 {
-            score += self.w.p_blocked[1];
-        }
-        score += (pattacks[0] & (mptr.attacks_king[kbits[1]] | bptr[K2])).count_ones() as i32 * self.w.g_atk_near_king[0][0];
-        score += (pattacks[1] & (mptr.attacks_king[kbits[0]] | bptr[K ])).count_ones() as i32 * self.w.g_atk_near_king[1][0];
-        score += (pattacks[0] & CENTER[0]).count_ones() as i32 * self.w.p_atk_center[0];
-        score += (pattacks[1] & CENTER[1]).count_ones() as i32 * self.w.p_atk_center[1];
-        let mut outpost_sqs = [pattacks[0] & STRONG[0], pattacks[1] & STRONG[1]];
-        for (ally, mut bb) in outpost_sqs.into_iter().enumerate() {
-            let enemy = (ally == 0) as usize;
-            while bb != 0 {
-                let sq = pop_bit(&mut bb);
-                if mptr.flanks[sq] & mptr.fwd[ally][sq] & bptr[P | enemy] != 0 {
-                    del_bit(&mut outpost_sqs[ally], sq);
-                    continue;
-                }
-                score += self.w.p_outpost[ally];
-                if mptr.step_pawns[ally][sq] & bptr[P | enemy] != 0 {
-                    score += self.w.p_outpost_block[enemy];
-                }
-            }
+            add!(Pawns, 1, self.w.p_blocked[1]);
         }
+        attacked_by[0][0] = pattacks[0];
+        attacked_by[1][0] = pattacks[1];
+        units[0] += (pattacks[0] & zone[1]).count_ones() as i32 * self.w.k_atk_units[0];
+        units[1] += (pattacks[1] & zone[0]).count_ones() as i32 * self.w.k_atk_units[0];
 
         for (ally, mut bb) in [bptr[Q], bptr[Q2]].into_iter().enumerate() {
             let enemy = (ally == 0) as usize;
             while bb != 0 {
                 let sq = pop_bit(&mut bb);
-                score_pd[0] += self.w.heatmap[0][Q | ally][sq];
-                score_pd[1] += self.w.heatmap[1][Q | ally][sq];
                 
-                let opr = self.board.get_sliding_straight_opportunities(sq, occup) & self.board.get_sliding_diagonal_opportunities(sq, occup);
+                let opr = self.board.get_sliding_straight_opportunities(sq, occup) | self.board.get_sliding_diagonal_opportunities(sq, occup);
                 let atk = opr & !sides[ally];
-                mobility[ally] += atk.count_ones();
+                let moves = (opr & area[ally]).count_ones() as usize;
+                add_pd!(Mobility, ally, self.w.s_mob_queen[0][ally][moves], self.w.s_mob_queen[1][ally][moves]);
+
+                // out before the minor pieces
+                if sq != (if ally == 0 { 3 } else { 59 }) {
+                    let home = (HOME[ally] & (bptr[N | ally] | bptr[B | ally])).count_ones() as i32;
+                    add_pd!(Pieces, ally, self.w.q_early[0][ally] * home, self.w.q_early[1][ally] * home);
+                }
 
                 if get_bit(sof[ally], sq) == 0 {
                     if get_bit(sof[enemy], sq) == 0 {
-                        score += self.w.rq_open[ally];
+                        add!(RookFiles, ally, self.w.rq_open[ally]);
                     } else {
-                        score += self.w.rq_semiopen[ally];
+                        add!(RookFiles, ally, self.w.rq_semiopen[ally]);
                     }
                 }
                 if atk & !sof[ally] != 0 {
                     if atk & (!sof[ally] & !sof[enemy]) != 0 {
-                        score += self.w.rq_atk_open[ally];
+                        add!(RookFiles, ally, self.w.rq_atk_open[ally]);
                     } else {
-                        score += self.w.rq_atk_semiopen[ally];
+                        add!(RookFiles, ally, self.w.rq_atk_semiopen[ally]);
                     }
                 }
-                if opr & (mptr.attacks_king[kbits[enemy]] | bptr[K | enemy]) != 0 {
-                    score += self.w.g_atk_near_king[ally][4];
+                let hits = opr & zone[enemy];
+                if hits != 0 {
+                    attackers[ally] += 1;
+                    units[ally] += hits.count_ones() as i32 * self.w.k_atk_units[4];
                 }
+                attacked_by[ally][4] |= opr;
                 if atk & (ppt[enemy] | ppt[ally]) != 0 {
-                    score += self.w.g_atk_ppt[ally];
+                    add!(Passed, ally, self.w.g_atk_ppt[ally]);
                 }
                 if get_bit(ppt[enemy], sq) != 0 {
-                    score += self.w.g_ppawn_block[ally];
+                    add!(Passed, ally, self.w.g_ppawn_block[ally]);
                 }
                 if opr & CENTER[ally] != 0 {
-                    score_pd[0] += self.w.g_atk_center[0][ally];
-                    score_pd[1] += self.w.g_atk_center[1][ally];
+                    add_pd!(Center, ally, self.w.g_atk_center[0][ally], self.w.g_atk_center[1][ally]);
                 }
 
                 let mut rook_pinned_to   = self.board.get_sliding_straight_attacks(sq, occup & !atk, sides[ally]) & !atk & bptr[K | enemy];
                 let mut bishop_pinned_to = self.board.get_sliding_diagonal_attacks(sq, occup & !atk, sides[ally]) & !atk & bptr[K | enemy];
                 while rook_pinned_to != 0 {
                     let csq = pop_bit(&mut rook_pinned_to);
-                    pins[enemy] |= self.get_sliding_diagonal_path_unsafe(sq, csq) & rpin[enemy];
+                    pins[enemy] |= self.get_sliding_straight_path_unsafe(sq, csq) & rpin[enemy];
                 }
                 while bishop_pinned_to != 0 {
                     let csq = pop_bit(&mut bishop_pinned_to);
                     pins[enemy] |= self.get_sliding_diagonal_path_unsafe(sq, csq) & bpin[enemy];
                 }
-
-                let profit = atk & bptr[K | enemy];
-                if profit != 0 {
-                    score += self.w.g_atk_pro[ally];
-                }
             }
         }
 
@@ -1015,58 +1557,67 @@ impl Chara {
             let enemy = (ally == 0) as usize;
             while bb != 0 {
                 let sq = pop_bit(&mut bb);
-                score_pd[0] += self.w.heatmap[0][R | ally][sq];
-                score_pd[1] += self.w.heatmap[1][R | ally][sq];
 
                 let opr = self.board.get_sliding_straight_opportunities(sq, occup);
                 let atk = opr & !sides[ally];
-                mobility[ally] += atk.count_ones();
+                let moves = (opr & area[ally]).count_ones() as usize;
+                add_pd!(Mobility, ally, self.w.s_mob_rook[0][ally][moves], self.w.s_mob_rook[1][ally][moves]);
+
+                let rank = if ally == 0 { sq >> 3 } else { 7 - (sq >> 3) };
+                let king_rank = if ally == 0 { kbits[enemy] >> 3 } else { 7 - (kbits[enemy] >> 3) };
+                if rank == 6 && king_rank == 7 {
+                    add_pd!(Pieces, ally, self.w.r_seventh[0][ally], self.w.r_seventh[1][ally]);
+                }
+                // behind a passer is from where it came: below ours, above theirs
+                let mut passers = (pass[0] | pass[1]) & opr & mptr.files[sq];
+                while passers != 0 {
+                    let csq = pop_bit(&mut passers);
+                    if (get_bit(pass[0], csq) != 0) == (sq < csq) {
+                        add_pd!(Passed, ally, self.w.r_behind_passer[0][ally], self.w.r_behind_passer[1][ally]);
+                    }
+                }
+                // shut in by the king, unless it can still castle that way
+                let ksq = kbits[ally];
+                let rights = if ally == 0 { [CLW, CSW] } else { [CLB, CSB] };
+                if moves <= 3 && rank == 0 && sq >> 3 == ksq >> 3 && ((ksq & 7) < 4) == ((sq & 7) < (ksq & 7)) && self.board.castlings & rights[(sq > ksq) as usize] == 0 {
+                    let times = 1 + (self.board.castlings & (rights[0] | rights[1]) == 0) as i32;
+                    add_pd!(Pieces, ally, self.w.r_trapped[0][ally] * times, self.w.r_trapped[1][ally] * times);
+                }
                 
                 if get_bit(sof[ally], sq) == 0 {
                     if get_bit(sof[enemy], sq) == 0 {
-                        score += self.w.rq_open[ally];
+                        add!(RookFiles, ally, self.w.rq_open[ally]);
                     } else {
-                        score += self.w.rq_semiopen[ally];
+                        add!(RookFiles, ally, self.w.rq_semiopen[ally]);
                     }
                 }
                 if atk & !sof[ally] != 0 {
                     if atk & (!sof[ally] & !sof[enemy]) != 0 {
-                        score += self.w.rq_atk_open[ally];
+                        add!(RookFiles, ally, self.w.rq_atk_open[ally]);
                     } else {
-                        score += self.w.rq_atk_semiopen[ally];
+                        add!(RookFiles, ally, self.w.rq_atk_semiopen[ally]);
                     }
                 }
-                if opr & (mptr.attacks_king[kbits[enemy]] | bptr[K | enemy]) != 0 {
-                    score += self.w.g_atk_near_king[ally][3];
+                let hits = opr & zone[enemy];
+                if hits != 0 {
+                    attackers[ally] += 1;
+                    units[ally] += hits.count_ones() as i32 * self.w.k_atk_units[3];
                 }
+                attacked_by[ally][3] |= opr;
                 if atk & (ppt[enemy] | ppt[ally]) != 0 {
-                    score += self.w.g_atk_ppt[ally];
-                }
-                if atk & ppt[ally] & rvic[enemy] != 0 {
-                    score += self.w.g_atk_pro_ppb[ally];
+                    add!(Passed, ally, self.w.g_atk_ppt[ally]);
                 }
                 if get_bit(ppt[enemy], sq) != 0 {
-                    score += self.w.g_ppawn_block[ally];
+                    add!(Passed, ally, self.w.g_ppawn_block[ally]);
                 }
                 if opr & CENTER[ally] != 0 {
-                    score_pd[0] += self.w.g_atk_center[0][ally];
-                    score_pd[1] += self.w.g_atk_center[1][ally];
+                    add_pd!(Center, ally, self.w.g_atk_center[0][ally], self.w.g_atk_center[1][ally]);
                 }
                 
                 let mut pinned_to = self.board.get_sliding_straight_attacks(sq, occup & !atk, sides[ally]) & !atk & rvic[enemy];
                 while pinned_to != 0 {
                     let csq = pop_bit(&mut pinned_to);
-                    pins[enemy] |= self.get_sliding_diagonal_path_unsafe(sq, csq) & rpin[enemy];
-                }
-
-                let mut profit = atk & rvic[enemy];
-                if profit != 0 {
-                    pop_bit(&mut profit);
-                    if profit != 0 {
-                        score += self.w.g_atk_pro[ally];
-                    } else {
-                        score += self.w.g_atk_pro_double[ally];
-                    }
+                    pins[enemy] |= self.get_sliding_straight_path_unsafe(sq, csq) & rpin[enemy];
                 }
             }
         }
@@ -1075,34 +1626,41 @@ impl Chara {
             let enemy = (ally == 0) as usize;
             while bb != 0 {
                 let sq = pop_bit(&mut bb);
-                score_pd[0] += self.w.heatmap[0][B | ally][sq];
-                score_pd[1] += self.w.heatmap[0][B | ally][sq];
 
                 let opr = self.board.get_sliding_diagonal_opportunities(sq, occup);
                 let atk = opr & !sides[ally];
-                mobility[ally] += atk.count_ones();
+                let moves = (opr & area[ally]).count_ones() as usize;
+                add_pd!(Mobility, ally, self.w.s_mob_bishop[0][ally][moves], self.w.s_mob_bishop[1][ally][moves]);
+
+                let colour = if get_bit(LIGHT_SQUARES, sq) != 0 { LIGHT_SQUARES } else { !LIGHT_SQUARES };
+                let bad = ((bptr[P | ally] & colour).count_ones() * (1 + (blocked[ally] & (FILE_C | FILE_D | FILE_E | FILE_F)).count_ones())) as i32;
+                add_pd!(Pieces, ally, self.w.b_bad[0][ally] * bad, self.w.b_bad[1][ally] * bad);
+                // a7 with b6 or h7 with g6
+                let rel = |x: usize| if ally == 0 { x } else { flip(x) };
+                if (sq == rel(48) && get_bit(bptr[P | enemy], rel(41)) != 0) || (sq == rel(55) && get_bit(bptr[P | enemy], rel(46)) != 0) {
+                    add_pd!(Pieces, ally, self.w.b_trapped[0][ally], self.w.b_trapped[1][ally]);
+                }
 
                 if get_bit(outpost_sqs[ally], sq) != 0 {
-                    score += self.w.nb_outpost[ally];
+                    add!(Outposts, ally, self.w.nb_outpost[ally]);
                 }
                 if outpost_sqs[ally] & atk != 0 {
-                    score += self.w.nb_outpost_reach[ally];
+                    add!(Outposts, ally, self.w.nb_outpost_reach[ally]);
                 }
-                if opr & (mptr.attacks_king[kbits[enemy]] | bptr[K | enemy]) != 0 {
-                    score += self.w.g_atk_near_king[ally][2];
+                let hits = opr & zone[enemy];
+                if hits != 0 {
+                    attackers[ally] += 1;
+                    units[ally] += hits.count_ones() as i32 * self.w.k_atk_units[2];
                 }
+                attacked_by[ally][2] |= opr;
                 if atk & (ppt[enemy] | ppt[ally]) != 0 {
-                    score += self.w.g_atk_ppt[ally];
-                }
-                if atk & ppt[ally] & bvic[enemy] != 0 {
-                    score += self.w.g_atk_pro_ppb[ally];
+                    add!(Passed, ally, self.w.g_atk_ppt[ally]);
                 }
                 if get_bit(ppt[enemy], sq) != 0 {
-                    score += self.w.g_ppawn_block[ally];
+                    add!(Passed, ally, self.w.g_ppawn_block[ally]);
                 }
                 if opr & CENTER[ally] != 0 {
-                    score_pd[0] += self.w.g_atk_center[0][ally];
-                    score_pd[1] += self.w.g_atk_center[1][ally];
+                    add_pd!(Center, ally, self.w.g_atk_center[0][ally], self.w.g_atk_center[1][ally]);
                 }
                 
                 let mut pinned_to = self.board.get_sliding_diagonal_attacks(sq, occup & !atk, sides[ally]) & !atk & bvic[enemy];
@@ -1110,16 +1668,6 @@ impl Chara {
                     let csq = pop_bit(&mut pinned_to);
                     pins[enemy] |= self.get_sliding_diagonal_path_unsafe(sq, csq) & bpin[enemy];
                 }
-
-                let mut profit = atk & bvic[enemy];
-                if profit != 0 {
-                    pop_bit(&mut profit);
-                    if profit != 0 {
-                        score += self.w.g_atk_pro[ally];
-                    } else {
-                        score += self.w.g_atk_pro_double[ally];
-                    }
-                }
             }
         }
 
@@ -1127,60 +1675,133 @@ impl Chara {
             let enemy = (ally == 0) as usize;
             while bb != 0 {
                 let sq = pop_bit(&mut bb);
-                score_pd[0] += self.w.heatmap[0][N | ally][sq];
-                score_pd[1] += self.w.heatmap[0][N | ally][sq];
 
                 let opr = mptr.attacks_knight[sq];
                 let atk = opr & !sides[ally];
-                mobility[ally] += atk.count_ones();
+                let moves = (opr & area[ally]).count_ones() as usize;
+                add_pd!(Mobility, ally, self.w.s_mob_knight[0][ally][moves], self.w.s_mob_knight[1][ally][moves]);
 
                 if get_bit(outpost_sqs[ally], sq) != 0 {
-                    score += self.w.nb_outpost[ally];
+                    add!(Outposts, ally, self.w.nb_outpost[ally]);
                 }
                 if outpost_sqs[ally] & mptr.attacks_knight[sq] != 0 {
-                    score += self.w.nb_outpost_reach[ally];
+                    add!(Outposts, ally, self.w.nb_outpost_reach[ally]);
                 }
-                if opr & (mptr.attacks_king[kbits[enemy]] | bptr[K | enemy]) != 0 {
-                    score += self.w.g_atk_near_king[ally][1];
+                let hits = opr & zone[enemy];
+                if hits != 0 {
+                    attackers[ally] += 1;
+                    units[ally] += hits.count_ones() as i32 * self.w.k_atk_units[1];
                 }
+                attacked_by[ally][1] |= opr;
                 if atk & (ppt[enemy] | ppt[ally]) != 0 {
-                    score += self.w.g_atk_ppt[ally];
-                }
-                if atk & ppt[ally] & bvic[enemy] != 0 {
-                    score += self.w.g_atk_pro_ppb[ally];
+                    add!(Passed, ally, self.w.g_atk_ppt[ally]);
                 }
                 if get_bit(ppt[enemy], sq) != 0 {
-                    score += self.w.g_ppawn_block[ally];
+                    add!(Passed, ally, self.w.g_ppawn_block[ally]);
                 }
                 if opr & CENTER[ally] != 0 {
-                    score_pd[0] += self.w.g_atk_center[0][ally];
-                    score_pd[1] += self.w.g_atk_center[1][ally];
-                }
-
-                let mut profit = atk & bvic[enemy];
-                if profit != 0 {
-                    pop_bit(&mut profit);
-                    if profit != 0 {
-                        score += self.w.g_atk_pro[ally];
-                    } else {
-                        score += self.w.g_atk_pro_double[ally];
-                    }
+                    add_pd!(Center, ally, self.w.g_atk_center[0][ally], self.w.g_atk_center[1][ally]);
                 }
             }
         }
 
         // lazy ^ 2 checks, not even count bits :(
         if pattacks[0] & pins[1] != 0 {
-            score += self.w.g_atk_pro_pinned[0];
+            add!(Pins, 0, self.w.g_atk_pro_pinned[0]);
         }
         if pattacks[1] & pins[0] != 0 {
-            score += self.w.g_atk_pro_pinned[1];
+            add!(Pins, 1, self.w.g_atk_pro_pinned[1]);
         }
         if pattacks[0] & ppt[0] & sides[1] & !bptr[P2] != 0 {
-            score += self.w.g_ppawn_block[0];
+            add!(Passed, 0, self.w.g_ppawn_block[0]);
         }
         if pattacks[1] & ppt[1] & sides[0] & !bptr[P ] != 0 {
-            score += self.w.g_ppawn_block[1];
+            add!(Passed, 1, self.w.g_ppawn_block[1]);
+        }
+
+        // threats: weak enemies (attacked and not protected by a pawn), pawn attacks and pushes, pressure on the queen and contested squares
+        for ally in 0..2 {
+            let enemy = (ally == 0) as usize;
+            let ours   = attacked_by[ally].iter().fold(mptr.attacks_king[kbits[ally]], |acc, bb| acc | bb);
+            let theirs = attacked_by[enemy].iter().fold(mptr.attacks_king[kbits[enemy]], |acc, bb| acc | bb);
+            let pieces = sides[enemy] & !bptr[P | enemy] & !bptr[K | enemy];
+            let weak   = sides[enemy] & !bptr[K | enemy] & !attacked_by[enemy][0] & ours;
+            let victims = |targets: u64, weights: &[i32; 5]| -> i32 {
+                weights.iter().enumerate().map(|(i, weight)| (targets & bptr[(P + (i << 1)) | enemy]).count_ones() as i32 * weight).sum()
+            };
+            add!(Threats, ally, victims(((pieces & attacked_by[enemy][0]) | weak) & (attacked_by[ally][1] | attacked_by[ally][2]), &self.w.g_threat_minor[ally]));
+            add!(Threats, ally, victims(weak & attacked_by[ally][3], &self.w.g_threat_rook[ally]));
+            if weak & mptr.attacks_king[kbits[ally]] != 0 {
+                add!(Threats, ally, self.w.g_threat_king[ally]);
+            }
+            add!(Threats, ally, (weak & !theirs).count_ones() as i32 * self.w.g_hanging[ally]);
+            add!(Threats, ally, (theirs & ours & !attacked_by[enemy][0]).count_ones() as i32 * self.w.g_restricted[ally]);
+
+            // pawns on squares that are defended or not attacked, and pushes to such squares
+            let safe = !theirs | ours;
+            let start = if ally == 0 { RANK_2 } else { RANK_7 };
+            let mut safe_attacks = 0;
+            let mut push_attacks = 0;
+            let mut bb = bptr[P | ally];
+            while bb != 0 {
+                let sq = pop_bit(&mut bb);
+                if get_bit(safe, sq) != 0 {
+                    safe_attacks |= mptr.attacks_pawns[ally][sq];
+                }
+                let mut pushes = mptr.step_pawns[ally][sq] & !occup;
+                if pushes != 0 && get_bit(start, sq) != 0 {
+                    pushes |= mptr.step_pawns[ally][gtz(pushes)] & !occup;
+                }
+                pushes &= safe & !attacked_by[enemy][0];
+                while pushes != 0 {
+                    push_attacks |= mptr.attacks_pawns[ally][pop_bit(&mut pushes)];
+                }
+            }
+            add!(Threats, ally, (safe_attacks & pieces).count_ones() as i32 * self.w.g_threat_safe_pawn[ally]);
+            add!(Threats, ally, (push_attacks & pieces & !attacked_by[ally][0]).count_ones() as i32 * self.w.g_threat_push[ally]);
+
+            // squares to attack the queen from, a slider needs one the queen doesn't take back with impunity
+            if bptr[Q | enemy].count_ones() == 1 {
+                let qsq = gtz(bptr[Q | enemy]);
+                let targets = !sides[ally] & !attacked_by[enemy][0];
+                add!(Threats, ally, (mptr.attacks_knight[qsq] & attacked_by[ally][1] & targets).count_ones() as i32 * self.w.g_knight_on_queen[ally]);
+                let backed = attacked_by[enemy][..4].iter().fold(mptr.attacks_king[kbits[enemy]], |acc, bb| acc | bb);
+                let sliders = (self.board.get_sliding_diagonal_opportunities(qsq, occup) & attacked_by[ally][2]) | (self.board.get_sliding_straight_opportunities(qsq, occup) & attacked_by[ally][3]);
+                add!(Threats, ally, (sliders & targets & !backed).count_ones() as i32 * self.w.g_slider_on_queen[ally]);
+            }
+        }
+
+        // king safety: attacks on the king zone, safe checks and weak zone squares are units, their sum goes through the danger table
+        for ally in 0..2 {
+            let enemy = (ally == 0) as usize;
+            let ksq = kbits[enemy];
+            let attacked = attacked_by[ally].iter().fold(0, |acc, bb| acc | bb);
+            let defended = attacked_by[enemy].iter().fold(0, |acc, bb| acc | bb);
+            let safe = !sides[ally] & !defended & !mptr.attacks_king[ksq];
+            let diagonal = self.board.get_sliding_diagonal_opportunities(ksq, occup);
+            let straight = self.board.get_sliding_straight_opportunities(ksq, occup);
+            let checks = [mptr.attacks_knight[ksq], diagonal, straight, diagonal | straight];
+            for (i, check) in checks.into_iter().enumerate() {
+                units[ally] += (check & attacked_by[ally][i + 1] & safe).count_ones() as i32 * self.w.k_check_units[i];
+            }
+            units[ally] += (zone[enemy] & attacked & !defended).count_ones() as i32 * self.w.k_weak_units;
+            // a lone attacker is rarely dangerous
+            if attackers[ally] < 2 {
+                units[ally] /= 2;
+            }
+            add!(KingAttack, ally, self.w.k_danger[ally][(units[ally] as usize).min(KING_DANGER_UNITS - 1)]);
+        }
+
+        // pawn shelter and storm in front of the king, or in front of a castling square if it's better there
+        for (ally, ksq) in kbits.into_iter().enumerate() {
+            let mut shelter = self.shelter(ally, ksq);
+            for (castle, sq) in [(CSW, 6), (CLW, 2)] {
+                if self.board.castlings & (castle << ally) != 0 {
+                    let castled = self.shelter(ally, sq ^ (ally * 56));
+                    shelter = if ally == 0 { max(shelter, castled) } else { min(shelter, castled) };
+                }
+            }
+            add_pd!(Shelter, ally, shelter, 0);
         }
 
         for (ally, mut bb) in [bptr[B], bptr[B2]].into_iter().enumerate() {
@@ -1190,7 +1811,7 @@ impl Chara {
                 let mut atk = self.board.get_sliding_diagonal_attacks(sq, occup, sides[ally]) & pins[enemy];
                 while atk != 0 {
                     pop_bit(&mut atk);
-                    score += self.w.g_atk_pro_pinned[ally];
+                    add!(Pins, ally, self.w.g_atk_pro_pinned[ally]);
                 }
             }
         }
@@ -1202,7 +1823,7 @@ impl Chara {
                 let mut atk = self.board.get_sliding_straight_attacks(sq, occup, sides[ally]) & pins[enemy];
                 while atk != 0 {
                     pop_bit(&mut atk);
-                    score += self.w.g_atk_pro_pinned[ally];
+                    add!(Pins, ally, self.w.g_atk_pro_pinned[ally]);
                 }
             }
         }
@@ -1214,63 +1835,83 @@ impl Chara {
                 let mut atk = (self.board.get_sliding_diagonal_attacks(sq, occup, sides[ally]) | self.board.get_sliding_straight_attacks(sq, occup, sides[ally])) & pins[enemy];
                 while atk != 0 {
                     pop_bit(&mut atk);
-                    score += self.w.g_atk_pro_pinned[ally];
+                    add!(Pins, ally, self.w.g_atk_pro_pinned[ally]);
                 }
             }
         }
 
-        score_pd[0] += self.w.heatmap[0][K ][kbits[0]];
-        score_pd[0] += self.w.heatmap[0][K2][kbits[1]];
-        score_pd[1] += self.w.heatmap[1][K ][kbits[0]];
-        score_pd[1] += self.w.heatmap[1][K2][kbits[1]];
+        // piece values and squares, the board sums them as it moves
+        add_pd!(Material, 0, self.board.psqt[0][0], self.board.psqt[0][1]);
+        add_pd!(Material, 1, self.board.psqt[1][0], self.board.psqt[1][1]);
 
-        score_pd[0] += self.w.k_mobility_as_q[0][0] * (self.board.get_sliding_diagonal_attacks(kbits[0], occup, sides[0]) | self.board.get_sliding_straight_attacks(kbits[0], occup, sides[0])).count_ones() as i32;
-        score_pd[0] += self.w.k_mobility_as_q[0][1] * (self.board.get_sliding_diagonal_attacks(kbits[1], occup, sides[1]) | self.board.get_sliding_straight_attacks(kbits[1], occup, sides[1])).count_ones() as i32;
+        add_pd!(King, 0, self.w.k_mobility_as_q[0][0] * (self.board.get_sliding_diagonal_attacks(kbits[0], occup, sides[0]) | self.board.get_sliding_straight_attacks(kbits[0], occup, sides[0])).count_ones() as i32, 0);
+        add_pd!(King, 1, self.w.k_mobility_as_q[0][1] * (self.board.get_sliding_diagonal_attacks(kbits[1], occup, sides[1]) | self.board.get_sliding_straight_attacks(kbits[1], occup, sides[1])).count_ones() as i32, 0);
         
         /* RANDOM DOESN'T APPLY FOR AN ENDSPIEL */
-        score_pd[0] -= self.w.rand;
-        score_pd[0] += self.rng.gen_range(0..=((self.w.rand as u32) << 1)) as i32;
+        add_pd!(Random, 0, self.rng.gen_range(0..=((self.w.rand as u32) << 1)) as i32 - self.w.rand, 0);
         
         if mptr.attacks_king[kbits[0]] & (pass[0] | pass[1]) != 0 {
-            score_pd[0] += self.w.k_pawn_dist1[0][0];
-            score_pd[1] += self.w.k_pawn_dist1[1][0];
+            add_pd!(Passed, 0, self.w.k_pawn_dist1[0][0], self.w.k_pawn_dist1[1][0]);
         } else if mptr.rad2[kbits[0]] & (pass[0] | pass[1]) != 0 {
-            score_pd[0] += self.w.k_pawn_dist2[0][0];
-            score_pd[1] += self.w.k_pawn_dist2[1][0];
+            add_pd!(Passed, 0, self.w.k_pawn_dist2[0][0], self.w.k_pawn_dist2[1][0]);
         }
         if mptr.attacks_king[kbits[1]] & (pass[0] | pass[1]) != 0 {
-            score_pd[0] += self.w.k_pawn_dist1[0][1];
-            score_pd[1] += self.w.k_pawn_dist1[1][1];
+            add_pd!(Passed, 1, self.w.k_pawn_dist1[0][1], self.w.k_pawn_dist1[1][1]);
         } else if mptr.rad2[kbits[1]] & (pass[0] | pass[1]) != 0 {
-            score_pd[0] += self.w.k_pawn_dist2[0][1];
-            score_pd[1] += self.w.k_pawn_dist2[1][1];
+            add_pd!(Passed, 1, self.w.k_pawn_dist2[0][1], self.w.k_pawn_dist2[1][1]);
         }
         if bptr[P] | bptr[P2] != 0 && ((kbits[0] & 7) as i32 - (kbits[1] & 7) as i32).abs() + ((kbits[0] >> 3) as i32  - (kbits[1] >> 3) as i32).abs() == 2 {
-            score_pd[0] += self.w.k_opposition[0][!self.board.turn as usize];
-            score_pd[1] += self.w.k_opposition[1][!self.board.turn as usize];
+            add_pd!(King, !self.board.turn as usize, self.w.k_opposition[0][!self.board.turn as usize], self.w.k_opposition[1][!self.board.turn as usize]);
         }
         if bptr[K] != 0 && bptr[Q] != 0 {
-            score += self.w.s_qnight[0];
+            add!(Pieces, 0, self.w.s_qnight[0]);
         }
         if bptr[K2] != 0 && bptr[Q2] != 0 {
-            score += self.w.s_qnight[1];
+            add!(Pieces, 1, self.w.s_qnight[1]);
         }
         if bptr[B] != 0 && (bptr[B] & (bptr[B] - 1)) != 0 {
-            score += self.w.s_bishop_pair[0];
+            add!(Pieces, 0, self.w.s_bishop_pair[0]);
         }
         if bptr[B2] != 0 && (bptr[B2] & (bptr[B2] - 1)) != 0 {
-            score += self.w.s_bishop_pair[1];
+            add!(Pieces, 1, self.w.s_bishop_pair[1]);
         }
 
         score += ((score_pd[0] as f32 * phase_diff) + (score_pd[1] as f32 * (1.0 - phase_diff))) as i32;
-        score += self.w.s_mobility * (mobility[0].count_ones() as i32 - mobility[1].count_ones() as i32);
 
+        // drawish (or clearly won) endgames scale the score of the side it favours
+        if score != 0 {
+            let strong = (score < 0) as usize;
+            let scale = match recognizer {
+                Some((Recognizer::Scale(scale), side)) if side == strong => scale(&self.board, strong),
+                Some(_) => SCALE_NORMAL,
+                None => endgame::scale(&self.board, strong)
+            };
+            if scale != SCALE_NORMAL {
+                add!(Endgame, strong, score * scale / SCALE_NORMAL - score);
+            }
+            // a dead draw gets no tempo either
+            if scale == SCALE_DRAW {
+                if let Some(trace) = trace {
+                    trace.phase = phase_diff;
+                }
+                return 0;
+            }
+        }
+
+        let turn = self.board.turn as usize;
+        if let Some(trace) = trace.as_deref_mut() {
+            trace.phase = phase_diff;
+            trace.tapered = score;
+        }
         if self.board.turn ^ (score > 0) {
-            score += score / self.w.s_turn_div;
+            add!(Tempo, turn, score / self.w.s_turn_div);
         } else {
-            score -= score / self.w.s_turn_div;
+            add!(Tempo, turn, -(score / self.w.s_turn_div));
+        }
+        add!(Tempo, turn, self.w.s_turn[turn]);
+        if let Some(trace) = trace {
+            trace.total = score;
         }
-        score += self.w.s_turn[self.board.turn as usize];
 
         /* SCORE APPLICATION END */
         
@@ -1281,8 +1922,71 @@ impl Chara {
         score
     }
 
+    // static eval of the current position term by term, its total is exactly what eval() gives (from white's view)
+    fn trace(&mut self) -> Trace {
+        let mut trace = Trace::default();
+        self.eval_traced(Some(&mut trace));
+        trace
+    }
+
+    // None if eval() of the position is exactly the negated eval() of its colour flipped copy (white's view),
+    // else a report with both totals and the terms that differ
+    fn asymmetry(&mut self, board: &Board) -> Option<String> {
+        let rand = self.w.rand;
+        self.w.rand = 0;
+        let mut board = board.clone();
+        board.set_heatmap(Some(self.heatmap.clone()));
+        let saved = std::mem::replace(&mut self.board, board.clone());
+        let trace = self.trace();
+        self.board = board.color_flipped();
+        let flipped = self.trace();
+        self.board = saved;
+        self.w.rand = rand;
+
+        if trace.total == -flipped.total && trace.asymmetric_terms(&flipped).is_empty() {
+            return None;
+        }
+        Some(format!("{}: {} vs {} flipped, terms: {}", board.export(), trace.total, -flipped.total, trace.asymmetric_terms(&flipped).join(", ")))
+    }
+
     /* Auxiliary (used by eval()) */
 
+    // our pawns in front of the king and enemy pawns coming at it on the king file and the two next to it (colour-signed)
+    fn shelter(&self, ally: usize, ksq: usize) -> i32 {
+        let enemy = (ally == 0) as usize;
+        let bptr = &self.board.bbs;
+        let mptr = &self.board.maps;
+        let ahead = mptr.fwd[ally][ksq] | mptr.ranks[ksq] | (1 << ksq);
+        // nearest to our side pawn and its rank from our side, 0 if there's none
+        let nearest = |bb: u64| {
+            if bb == 0 {
+                0
+            } else if ally == 0 {
+                gtz(bb) >> 3
+            } else {
+                7 - ((63 - bb.leading_zeros() as usize) >> 3)
+            }
+        };
+
+        let mut score = 0;
+        let center = (ksq & 7).clamp(1, 6);
+        for file in center - 1..=center + 1 {
+            let sq = (ksq & !7) | file;
+            let column = (mptr.files[sq] | (1 << sq)) & ahead;
+            let ours = nearest(column & bptr[P | ally]);
+            let theirs = nearest(column & bptr[P | enemy]);
+            score += self.w.k_shelter[ally][ours];
+            if ours == 0 && theirs == 0 {
+                score += self.w.k_open_file[ally];
+            } else if ours != 0 && theirs == ours + 1 {
+                score += self.w.k_storm_blocked[ally];
+            } else {
+                score += self.w.k_storm[ally][theirs];
+            }
+        }
+        score
+    }
+
     #[inline]
     fn get_sliding_straight_path_unsafe(&self, sq1: usize, sq2: usize) -> u64 {
         self.board.get_sliding_straight_attacks(sq1, 1 << sq2, 0) & self.board.get_sliding_straight_attacks(sq2, 1 << sq1, 0)
@@ -1295,39 +1999,48 @@ impl Chara {
 
     /* Play functions */
 
-    fn considerate_draw(&self, wadd: i32) -> bool {
-        let pl = if self.playother {
-            -1
-        } else {
-            1
-        };
-        let mut weight_for_draw = -self.last_score * pl;
-        let pieces_left = (self.board.get_occupancies(false) | self.board.get_occupancies(true)).count_ones();
-        if pieces_left < 5 && weight_for_draw > -300 {
-            return true;
-        }
-        if pieces_left > 24 {
-            weight_for_draw -= 600;
-        }
-        else if pieces_left > 16 {
-            weight_for_draw -= 400;
-        } else if pieces_left > 8 {
-            weight_for_draw -= 200;
+    // contempt for the engine in score units, opfor strength and personality shift it
+    // dynamic: full in the middlegame and half of it in the endgame, more when ahead and none when 2 pawns down
+    fn calc_contempt(&mut self) -> i32 {
+        let mut contempt = (self.options.contempt + self.opponent.contempt() + self.options.personality.contempt) * 4;
+        if self.options.contempt_dynamic {
+            let phase = min(max(18, self.board.phase) - 18, 38) as i32;
+            let eval = if self.playother {
+                -self.eval()
+            } else {
+                self.eval()
+            };
+            contempt = contempt * (38 + phase) / 76;
+            contempt = contempt * (eval + 800).clamp(0, 1600) / 800;
         }
-        // weight_for_draw += self.clock.is_it_time_for_draw() * pl;
-        if self.board.hmc > 20 {
-            weight_for_draw += 200;
-            if self.board.hmc > 40 {
-                weight_for_draw += 300;
-                if self.board.hmc > 60 {
-                    weight_for_draw += 400;
-                }
-            }
+        contempt
+    }
+
+    // draws are worth -contempt for the engine and +contempt for opfor
+    #[inline]
+    fn draw_score(&self) -> i32 {
+        if self.board.turn == self.started_black {
+            self.draw_root
+        } else {
+            -self.draw_root
         }
-        weight_for_draw -= (self.w.rand - 2) * pl;
-        weight_for_draw += wadd;
-        println!("#DEBUG\tCalculated draw offer: {}", weight_for_draw);
-        weight_for_draw > 0
+    }
+
+    // what a draw is worth for us in centipawns (the same draw score the search uses)
+    fn draw_value(&self) -> i32 {
+        -score_to_gui(self.contempt, false)
+    }
+
+    // playother: if the last score is from opfor side
+    fn accept_draw(&self, playother: bool) -> bool {
+        let score = if playother {
+            -self.last_score
+        } else {
+            self.last_score
+        };
+        let accept = self.policy.should_accept_draw(score, self.draw_value(), self.clock.is_it_time_for_draw());
+        println!("#DEBUG\tDraw offer at score {}: {}", score, accept);
+        accept
     }
 
     fn get_result(&mut self) -> GameResult {
@@ -1339,34 +2052,90 @@ impl Chara {
                 }
                 return GameResult::BlackWon;
             }
-            return GameResult::Draw;
+            return GameResult::Draw("Stalemate");
         }
         if self.board.hmc > 99 {
-            return GameResult::Draw;
+            return GameResult::Draw("50 move rule");
+        }
+        if self.repetitions() >= 2 {
+            return GameResult::Draw("3-fold repetition");
+        }
+        if self.board.is_insufficient_material() {
+            return GameResult::Draw("Insufficient material");
         }
-        // TODO: autodraw on move repetition
         GameResult::InProgress
     }
 
+    // how many times the current position was already on the board (same side to move, no irreversible moves since)
+    fn repetitions(&self) -> usize {
+        let hash = *self.history_vec.last().unwrap();
+        self.history_vec.iter().rev().take(self.board.hmc as usize + 1).skip(2).step_by(2).filter(|&&prev| prev == hash).count()
+    }
+
     fn post(&self) {
-        if !self.post {
+        if !self.roots.is_empty() {
+            self.post_line(0);
+        }
+    }
+
+    fn post_line(&self, pv_idx: usize) {
+        let rm = &self.roots.moves[pv_idx];
+        if !self.post || rm.score == -INF {
             return;
         }
         let scu = if self.playother {
-            -self.last_score
+            -score_to_gui(rm.score, false)
         } else {
-            self.last_score
+            score_to_gui(rm.score, false)
         };
-        print!("{} {} {} {}", self.cur_depth, scu, self.ts.elapsed().as_millis() / 10, self.nodes);
-        for (i, mov) in self.tpv[0].iter().enumerate().take(max(self.tpv_len[0], 1)) {
+        print!("{} {} {} {}", rm.depth, scu, self.ts.elapsed().as_millis() / 10, self.nodes);
+        for (i, mov) in rm.pv.iter().enumerate() {
             print!(" {}", move_transform(*mov, (i & 1 != 0) ^ self.started_black));
         }
         println!();
     }
 
     #[inline]
-    fn time_alloc(&mut self) -> u128 {
-        self.clock.time_alloc(self.board.no, self.hard)
+    fn time_alloc(&self) -> TimeBudget {
+        self.clock.time_alloc(self.board.no, self.hard, self.options.move_overhead)
+    }
+
+    // search parameters first, then clock, policy and skill ones, then the usual options
+    fn set_option(&mut self, query: &str) {
+        let cmd = query.split('=').collect::<Vec<&str>>();
+        if cmd.len() == 2 && (self.sp.parse(cmd[0], cmd[1]) || self.clock.parse(cmd[0], cmd[1]) || self.policy.parse(cmd[0], cmd[1]) || self.skill.parse(cmd[0], cmd[1]) || self.solver.parse(cmd[0], cmd[1])) {
+            return;
+        }
+        self.options.parse(query);
+        if cmd[0] == "Personality" || cmd[0] == "WeightsFile" {
+            self.rebuild_weights();
+        }
+    }
+
+    // pawn structure, imbalance and static evals are cached with the weights they were evaluated with
+    fn clear_eval_caches(&mut self) {
+        self.pawn_cache.fill(PawnHash::default());
+        self.material_cache.fill(MaterialHash::default());
+        self.static_cache.fill(StaticHash::default());
+    }
+
+    // built-in weights, then the custom weights file, then the personality on top
+    fn rebuild_weights(&mut self) {
+        let rand = self.w.rand;
+        self.w = Weights::init();
+        if !self.options.weights_file.is_empty() {
+            if let Err(error) = self.w.load(&self.options.weights_file) {
+                println!("Error (cannot load weights): {}", error);
+            }
+        }
+        self.w.apply_personality(&self.options.personality);
+        self.w.rand = rand;
+        self.sp.risk = self.options.personality.risk;
+        self.sp.init_lmr();
+        self.clear_eval_caches();
+        self.heatmap = Arc::new(self.w.heatmap);
+        self.board.set_heatmap(Some(self.heatmap.clone()));
+        println!("#DEBUG\tWeights rebuilt: personality {}, file \"{}\"", self.options.personality.name, self.options.weights_file);
     }
 }
 
@@ -1378,7 +2147,7 @@ mod tests {
 
     #[test]
     fn test_chara_eval_initial_1() {
-        let (tx, rx) = channel();
+        let (_tx, rx) = channel();
         let mut chara = Chara::init("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", rx);
         let moves = chara.board.get_legal_moves();
         chara.make_move(move_transform_back("e2e4", &moves, chara.board.turn).unwrap());
@@ -1390,6 +2159,113 @@ mod tests {
         assert_eq!(eval, cur);
     }
 
+    #[test]
+    fn test_chara_multipv() {
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init("4k3/8/8/3q4/8/8/8/3QK3 w - - 0 1", rx);
+        chara.options.multipv = 3;
+        let em = chara.think(chara.baw, TimeBudget::unlimited(), 4);
+        let moves = chara.board.get_legal_moves();
+        assert_eq!(em.mov, move_transform_back("d1d5", &moves, chara.board.turn).unwrap());
+
+        let lines = &chara.roots.moves[..3];
+        for pair in lines.windows(2) {
+            assert_ne!(pair[0].mov, pair[1].mov);
+            assert!(pair[0].score >= pair[1].score);
+        }
+        for rm in lines.iter() {
+            assert_eq!(rm.depth, 4);
+            assert_eq!(rm.pv[0], rm.mov);
+        }
+    }
+
+    #[test]
+    fn test_chara_lmr_shallow() {
+        // late moves may be reduced right above the horizon, but never below it
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4", rx);
+        chara.sp.lmr_min_depth = 1;
+        chara.sp.lmr_min_moves = 1;
+        let em = chara.think(chara.baw, TimeBudget::unlimited(), 4);
+        assert!(chara.board.get_legal_moves().contains(&em.mov));
+    }
+
+    #[test]
+    fn test_chara_mate_input() {
+        // commands sent during a mate search are not lost, the one that stopped it included
+        let (tx, rx) = channel();
+        let mut chara = Chara::init("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", rx);
+        for line in ["mate 4", "post", "force", "quit"] {
+            tx.send(line.to_string()).unwrap();
+        }
+        chara.listen();
+        assert!(chara.post);
+        assert!(chara.force);
+        assert!(chara.quit);
+        assert!(chara.pending.is_empty());
+    }
+
+    #[test]
+    fn test_chara_result_repetition() {
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", rx);
+        for (i, mov) in ["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"].into_iter().enumerate() {
+            assert!(matches!(chara.get_result(), GameResult::InProgress), "{}", i);
+            let moves = chara.board.get_legal_moves();
+            chara.make_move(move_transform_back(mov, &moves, chara.board.turn).unwrap());
+        }
+        assert_eq!(chara.repetitions(), 2);
+        assert!(matches!(chara.get_result(), GameResult::Draw("3-fold repetition")));
+    }
+
+    #[test]
+    fn test_chara_contempt() {
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", rx);
+        chara.options.contempt = 50;
+        chara.think(chara.baw, TimeBudget::unlimited(), 1);
+        assert_eq!(chara.draw_score(), -200);
+        assert_eq!(chara.draw_value(), -50);
+        let moves = chara.board.get_legal_moves();
+        chara.make_move(moves[0]);
+        assert_eq!(chara.draw_score(), 200);
+
+        // pondering: root side is opfor
+        chara.playother = true;
+        chara.think(chara.baw, TimeBudget::unlimited(), 1);
+        assert_eq!(chara.draw_score(), 200);
+
+        // no contempt if we are a rook down, even less of it in the endgame
+        chara.options.contempt_dynamic = true;
+        chara.playother = false;
+        chara.set_pos("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/1NBQKBNR w Kkq - 0 1");
+        assert_eq!(chara.calc_contempt(), 0);
+        chara.set_pos("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1");
+        assert!(chara.calc_contempt() < 200);
+    }
+
+    #[test]
+    fn test_chara_skill() {
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init("4k3/8/8/3q4/8/8/8/3QK3 w - - 0 1", rx);
+        chara.skill.limit = true;
+        chara.skill.elo = 800;
+        let moves = chara.board.get_legal_moves();
+        for _ in 0..10 {
+            let em = chara.think(chara.baw, TimeBudget::unlimited(), HALF_DEPTH_LIMIT_SAFE);
+            assert!(moves.contains(&em.mov));
+            assert!(chara.nodes <= chara.skill.nodes());
+            assert!(chara.cur_depth <= chara.skill.depth() + 1);
+        }
+
+        // the queen is never left hanging at the top level
+        chara.skill.elo = 2400;
+        for _ in 0..3 {
+            let em = chara.think(chara.baw, TimeBudget::unlimited(), HALF_DEPTH_LIMIT_SAFE);
+            assert_eq!(em.mov, move_transform_back("d1d5", &moves, chara.board.turn).unwrap());
+        }
+    }
+
     #[test]
     fn test_chara_eval_initial_2() {
         let fens = [
@@ -1401,7 +2277,7 @@ mod tests {
             // "8/5P2/p3k3/6P1/1p6/3K3P/2p5/8 w - - 0 1" - assymetric because of PeSto
         ];
         for fen in fens.into_iter() {
-            let (tx, rx) = channel();
+            let (_tx, rx) = channel();
             let mut chara = Chara::init(fen, rx);
             let eval = chara.eval();
             let cur = chara.w.s_turn[0];
@@ -1409,7 +2285,7 @@ mod tests {
         }
 
         for fen in fens.into_iter() {
-            let (tx, rx) = channel();
+            let (_tx, rx) = channel();
             let mut board = Board::import(fen);
             board.turn = !board.turn;
             let mut chara = Chara::init(&board.export(), rx);
@@ -1430,17 +2306,268 @@ mod tests {
             "r1bqk2r/ppppbppp/2n2n2/4p3/2BPP3/5N2/PPP2PPP/RNBQ1RK1 b kq - 0 1"
         ];
         for i in 0..wfens.len() {
-            let (tx, rx) = channel();
+            let (_tx, rx) = channel();
             let mut chara1 = Chara::init(wfens[i], rx);
-            let (tx2, rx2) = channel();
+            let (_tx2, rx2) = channel();
             let mut chara2 = Chara::init(bfens[i], rx2);
             assert_eq!(chara1.eval(), chara2.eval());
         }
     }
 
+    #[test]
+    fn test_chara_eval_trace() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/pbppnpp1/1p1bn2p/4p1q1/4P1Q1/1P1BN2P/PBPPNPP1/R3K2R w KQkq - 2 11",
+            "r1bqk2r/ppppbppp/2n2n2/4p3/2BPP3/5N2/PPP2PPP/RNBQ1RK1 b kq - 0 1",
+            "8/5P2/p3k3/6P1/1p6/3K3P/2p5/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/8 b - - 0 1"
+        ];
+        for fen in fens.into_iter() {
+            let (_tx, rx) = channel();
+            let mut chara = Chara::init(fen, rx);
+            let trace = chara.trace();
+            let eval = chara.eval();
+            assert_eq!(if chara.board.turn { -trace.total } else { trace.total }, eval);
+
+            // everything before tempo is the tapered sum of the terms (up to rounding of every term)
+            let tapered = trace.terms[..trace.terms.len() - 1].iter().map(|values| {
+                let total = [values[0][0] + values[1][0], values[0][1] + values[1][1]];
+                total[0] as f32 * trace.phase + total[1] as f32 * (1.0 - trace.phase)
+            }).sum::<f32>();
+            assert!((tapered - trace.tapered as f32).abs() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_chara_static_cache() {
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init("rnb1kbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", rx);
+        let eval = chara.eval();
+        let hash = *chara.history_vec.last().unwrap();
+        assert_eq!(chara.static_cache[(hash & STATIC_CACHE_BITMASK) as usize].score, eval);
+        // a queen up is far above a window around zero: the bound is returned and nothing is cached
+        chara.static_cache.fill(StaticHash::default());
+        let margin = chara.sp.lazy_margin;
+        assert_eq!(chara.eval_lazy(-100, 100), chara.eval_rough() - margin);
+        assert_eq!(chara.static_cache[(hash & STATIC_CACHE_BITMASK) as usize].hash, 0);
+        assert_eq!(chara.eval_lazy(-LARGE, LARGE), eval);
+        chara.sp.lazy_margin = 0;
+        chara.static_cache.fill(StaticHash::default());
+        assert_eq!(chara.eval_lazy(-100, 100), eval);
+        // endings are evaluated in full
+        chara.set_pos("8/8/4k3/8/8/3NK3/8/8 w - - 0 1");
+        chara.sp.lazy_margin = 2000;
+        assert_eq!(chara.eval_lazy(-LARGE, -LARGE + 1), 0);
+        // with Random on, the same position gets new noise every time
+        chara.set_pos("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        chara.w.rand = 80;
+        let first = chara.eval();
+        assert!((0..20).any(|_| chara.eval() != first));
+        assert!((0..20).any(|_| chara.eval_lazy(-LARGE, LARGE) != first));
+    }
+
+    #[test]
+    fn test_chara_pawn_cache() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", rx);
+        let mut rng = StdRng::seed_from_u64(1773);
+        let mut made = 0;
+        for _ in 0..60 {
+            let moves = chara.board.get_legal_moves();
+            if moves.is_empty() {
+                break;
+            }
+            chara.make_move(moves[rng.gen_range(0..moves.len())]);
+            made += 1;
+            assert_eq!(*chara.pawn_vec.last().unwrap(), chara.zobrist.pawn_new(&chara.board));
+            // the trace never takes the cache, the first eval fills it, the second one takes it (not the static eval one)
+            let total = chara.trace().total;
+            let uncached = if chara.board.turn { -total } else { total };
+            assert_eq!(chara.eval(), uncached);
+            chara.static_cache.fill(StaticHash::default());
+            assert_eq!(chara.eval(), uncached);
+        }
+        for _ in 0..made {
+            chara.revert_move();
+            assert_eq!(*chara.pawn_vec.last().unwrap(), chara.zobrist.pawn_new(&chara.board));
+            let total = chara.trace().total;
+            chara.static_cache.fill(StaticHash::default());
+            assert_eq!(chara.eval(), if chara.board.turn { -total } else { total });
+        }
+    }
+
+    #[test]
+    fn test_chara_endgames() {
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init("8/8/8/8/8/8/8/8 w - - 0 1", rx);
+        let mut eval = |fen: &str| {
+            chara.set_pos(fen);
+            chara.eval()
+        };
+        // the right corner for a dark bishop is a1 or h8
+        assert!(eval("7k/8/5K2/8/8/8/8/2B1N3 w - - 0 1") > eval("k7/8/2K5/8/8/8/8/2B1N3 w - - 0 1"));
+        assert!(eval("7k/8/5K2/8/8/8/8/3R4 w - - 0 1") > endgame::KNOWN_WIN);
+        assert!(eval("8/8/3k4/8/8/3K4/8/3r4 w - - 0 1") < -endgame::KNOWN_WIN);
+        assert!(eval("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1") < -endgame::KNOWN_WIN);
+        assert_eq!(eval("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1"), 0);
+
+        // Philidor is a draw, Lucena is a win
+        assert_eq!(eval("3k4/8/7r/3PK3/8/8/8/6R1 w - - 0 1"), 0);
+        assert_eq!(eval("3k4/8/r7/3PK3/8/8/8/6R1 b - - 0 1"), 0);
+        assert!(eval("3K4/3P1k2/8/8/8/8/1r6/4R3 w - - 0 1") > eval("3K4/3P4/8/8/8/8/1r6/4R1k1 w - - 0 1") / 2);
+
+        // a wrong bishop, opposite coloured bishops and two knights
+        assert_eq!(eval("k7/8/8/P7/8/8/8/4K1B1 w - - 0 1"), 0);
+        assert!(eval("k7/8/8/P7/8/8/8/4KB2 w - - 0 1") > 400);
+        let ocb = eval("4k3/8/4b3/2P5/1P6/4B3/8/4K3 w - - 0 1");
+        let scb = eval("4k3/8/3b4/2P5/1P6/4B3/8/4K3 w - - 0 1");
+        assert!(ocb > 0 && ocb < scb / 2);
+        assert_eq!(eval("8/8/4k3/8/8/2NNK3/8/8 w - - 0 1"), 0);
+    }
+
+    // one term of the traced eval, [colour][phase], and the weights it was evaluated with
+    fn term(fen: &str, term: Term) -> ([[i32; 2]; 2], Weights) {
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init(fen, rx);
+        let trace = chara.trace();
+        (trace.terms[term as usize], chara.w)
+    }
+
+    #[test]
+    fn test_chara_mobility() {
+        let (free, w) = term("4k3/7p/8/8/3N4/8/7P/4K3 w - - 0 1", Term::Mobility);
+        assert_eq!(free[0], [w.s_mob_knight[0][0][8], w.s_mob_knight[1][0][8]]);
+        // c6 and e6 are taken by the pawn on d7
+        let (pawn, _) = term("4k3/3p3p/8/8/3N4/8/7P/4K3 w - - 0 1", Term::Mobility);
+        assert_eq!(pawn[0], [w.s_mob_knight[0][0][6], w.s_mob_knight[1][0][6]]);
+        // a pawn that can't move is no square for the bishop, b2 and a3 are
+        let (blocked, _) = term("4k3/8/8/8/8/3p4/3P4/2B1K3 w - - 0 1", Term::Mobility);
+        assert_eq!(blocked[0], [w.s_mob_bishop[0][0][2], w.s_mob_bishop[1][0][2]]);
+    }
+
+    #[test]
+    fn test_chara_placement() {
+        let pieces = |fen: &str| term(fen, Term::Pieces).0[0][0];
+        let (_, w) = term("4k3/R6p/8/8/8/8/7P/4K3 w - - 0 1", Term::Pieces);
+        assert_eq!(pieces("4k3/R6p/8/8/8/8/7P/4K3 w - - 0 1") - pieces("4k3/7p/R7/8/8/8/7P/4K3 w - - 0 1"), w.r_seventh[0][0]);
+        assert_eq!(pieces("4k3/B6p/1p6/8/8/8/7P/4K3 w - - 0 1") - pieces("4k3/B6p/8/1p6/8/8/7P/4K3 w - - 0 1"), w.b_trapped[0][0]);
+        // the queen is out with all four minor pieces at home
+        assert_eq!(pieces("4k3/7p/8/8/8/3Q4/7P/1NB1KBN1 w - - 0 1") - pieces("4k3/7p/8/8/8/8/7P/1NBQKBN1 w - - 0 1"), w.q_early[0][0] * 4);
+    }
+
+    #[test]
+    fn test_chara_pins() {
+        // the knight on e5 is pinned by the rook on a file, the pawn on d4 and the rook attack it
+        let (pinned, w) = term("4k3/7p/8/4n3/3P4/8/7P/4RK2 w - - 0 1", Term::Pins);
+        assert_eq!(pinned[0][0], w.g_atk_pro_pinned[0] * 2);
+        let (free, _) = term("3k4/7p/8/4n3/3P4/8/7P/4RK2 w - - 0 1", Term::Pins);
+        assert_eq!(free[0][0], 0);
+    }
+
+    #[test]
+    fn test_chara_threats() {
+        let threats = |fen: &str| term(fen, Term::Threats).0[0][0];
+        // the knight on d5 is attacked by a bishop: worse if it's hanging, even worse if a pawn attacks it
+        let (defended, w) = term("4k3/8/4p3/3n4/8/1B6/8/4K3 w - - 0 1", Term::Threats);
+        let defended = defended[0][0];
+        assert_eq!(defended, w.g_threat_minor[0][1]);
+        assert!(threats("4k3/8/8/3n4/8/1B6/8/4K3 w - - 0 1") - defended >= w.g_hanging[0]);
+        assert!(threats("4k3/8/8/3n4/2P5/8/8/4K3 w - - 0 1") >= w.g_threat_safe_pawn[0] + w.g_hanging[0]);
+        // c3-c4 would attack it
+        assert!(threats("4k3/8/8/8/3n4/8/2P5/4K3 w - - 0 1") >= w.g_threat_push[0]);
+        // Ba4, Bg4, Nc5 or Ne5 would hit the queen on d7
+        assert!(threats("4k3/3q4/8/8/8/3N4/8/3BK3 w - - 0 1") >= w.g_slider_on_queen[0] + w.g_knight_on_queen[0]);
+    }
+
+    #[test]
+    fn test_chara_king_safety() {
+        let king_attack = |fen: &str| term(fen, Term::KingAttack).0[0][0];
+        // queen and knight together vs the knight alone vs nothing
+        let coordinated = king_attack("r4rk1/ppp2ppp/8/6NQ/8/8/PPP2PPP/R5K1 w - - 0 1");
+        let lone = king_attack("r4rk1/ppp2ppp/8/6N1/8/8/PPP2PPP/Q5K1 w - - 0 1");
+        assert_eq!(king_attack("r4rk1/ppp2ppp/8/8/8/8/PPP2PPP/Q5K1 w - - 0 1"), 0);
+        assert!(lone > 0 && coordinated > lone * 10, "{} vs {}", coordinated, lone);
+        // a rook joins in
+        assert!(king_attack("r4rk1/ppp2pp1/7R/6NQ/8/8/PPP2PPP/6K1 w - - 0 1") > coordinated);
+    }
+
+    #[test]
+    fn test_chara_shelter() {
+        let shelter = |fen: &str| term(fen, Term::Shelter).0[0][0];
+        let intact = shelter("6k1/5ppp/8/8/8/8/5PPP/6K1 w - - 0 1");
+        assert!(intact > shelter("6k1/5ppp/8/8/8/6P1/5P2/6K1 w - - 0 1"));
+        // an enemy pawn close to the king is worse, unless it's stuck
+        assert!(shelter("6k1/5p1p/8/6p1/8/8/5P1P/6K1 w - - 0 1") > shelter("6k1/5p1p/8/8/8/6p1/5P1P/6K1 w - - 0 1"));
+        assert!(shelter("6k1/5p1p/8/8/8/6p1/5PPP/6K1 w - - 0 1") > shelter("6k1/5p1p/8/8/8/6p1/5P1P/6K1 w - - 0 1"));
+        // the king may still castle into a better shelter
+        assert_eq!(shelter("4k3/8/8/8/8/8/5PPP/4K2R w K - 0 1"), intact);
+        assert!(shelter("4k3/8/8/8/8/8/5PPP/4K2R w - - 0 1") < intact);
+    }
+
+    #[test]
+    fn test_chara_pawn_structure() {
+        let passed = |fen: &str| term(fen, Term::Passed).0[0][1];
+        // d2 is backward, e3 is not (it's supported by d2)
+        let (pawns, w) = term("4k3/8/8/8/2p5/4P3/3P4/4K3 w - - 0 1", Term::Pawns);
+        assert_eq!(pawns[0][1], w.p_backward[0]);
+
+        // the rule of the square: the king on a5 is too far, on b5 it is just in time (blocked pawns keep it from being KPK)
+        let outside = passed("8/8/8/k7/2p5/2P5/6P1/K7 w - - 0 1");
+        let inside = passed("8/8/8/1k6/2p5/2P5/6P1/K7 w - - 0 1");
+        assert!(outside - inside >= w.p_unstoppable[1][0]);
+        // ...but with the move it's inside
+        assert!(passed("8/8/8/k7/2p5/2P5/6P1/K7 b - - 0 1") < outside);
+
+        // connected and protected passers are worth more than split ones
+        assert!(passed("4k3/8/8/3PP3/8/8/8/4K3 w - - 0 1") > passed("4k3/8/8/2P1P3/8/8/8/4K3 w - - 0 1"));
+        assert!(passed("k7/8/8/3P4/4P3/8/8/7K w - - 0 1") > passed("k7/8/8/3P4/8/4P3/8/7K w - - 0 1"));
+    }
+
+    #[test]
+    fn test_chara_eval_symmetry() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        // random games from a few openings give a lot of varied positions
+        let starts = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"
+        ];
+        let (_tx, rx) = channel();
+        let mut chara = Chara::init(starts[0], rx);
+        let mut rng = StdRng::seed_from_u64(1773);
+        let mut count = 0;
+        let mut reports = Vec::new();
+        for start in starts.into_iter() {
+            let start = Board::import(start);
+            for _ in 0..10 {
+                let mut board = start.clone();
+                for _ in 0..80 {
+                    let moves = board.get_legal_moves();
+                    if moves.is_empty() {
+                        break;
+                    }
+                    board.make_move(moves[rng.gen_range(0..moves.len())]);
+                    reports.extend(chara.asymmetry(&board));
+                    count += 1;
+                }
+            }
+        }
+        assert!(reports.is_empty(), "{} of {} positions:\n{}", reports.len(), count, reports.join("\n"));
+
+        // and an asymmetric weight is caught
+        chara.w.p_doubled[1] -= 1;
+        let report = chara.asymmetry(&Board::import("4k3/1p6/1p6/8/8/1P6/1P6/4K3 w - - 0 1")).unwrap();
+        assert!(report.ends_with("terms: Pawn structure"), "{}", report);
+    }
+
     #[test]
     fn test_board_aux() {
-        let (tx, rx) = channel();
+        let (_tx, rx) = channel();
         let ar_true  = [[0, 7], [7, 0], [63, 7], [7, 63], [56, 63], [63, 56], [56, 0], [0, 56], [27, 51], [33, 38]];
         let chara = Chara::init("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", rx);
         for case in ar_true.into_iter() {
@@ -1453,8 +2580,8 @@ mod tests {
         }
 
         let board = Board::default();
-        assert_eq!(board.is_in_check(), false);
+        assert!(!board.is_in_check());
         let board = Board::import("rnb1kbnr/pppp1ppp/8/4p3/6Pq/5P2/PPPPP2P/RNBQKBNR w KQkq - 1 3");
-        assert_eq!(board.is_in_check(), true);
+        assert!(board.is_in_check());
     }
 }
\ No newline at end of file
@@ -1,21 +1,57 @@
 use std::cmp::{max, min};
+use crate::frame::util::PONDER_TIME;
 
-const TPS: [[u128;  2]; 2] = [[45, 75], [67, 100]]; // [safe/unsafe][no_ponder/ponder]
-const TDS: [[usize; 2]; 2] = [[ 7,  7], [ 6,  6]];  // [safe/unsafe][no_ponder/ponder]
+const MAX_RATIO: u128 = 3;                          // maximum time is up to this many optimums
+const MAX_SHARE: u128 = 80;                         // ...but never more than this % of the time left
+const SAFE_MOVES: u128 = 40;                        // moves ahead the overhead is reserved for in incremental games
+const PONDER_BONUS: u128 = 4;                       // +1/4 of optimum if we are pondering (opfor thinks on our time)
+
+// optimum: stop at this point if the best move looks settled (think() may scale it)
+// maximum: abort the search no matter what
+#[derive(Clone, Copy)]
+pub struct TimeBudget {
+    pub optimum:    u128,
+    pub maximum:    u128
+}
+
+impl TimeBudget {
+    pub fn unlimited() -> Self {
+        Self {
+            optimum: PONDER_TIME,
+            maximum: PONDER_TIME
+        }
+    }
+}
 
 enum TimeControl {
-    Conventional,
-    Incremental,
-    Deadline 
+    Periods,
+    Deadline
+}
+
+// what happens with the time spent on a move besides it's taken from our clock
+#[derive(Clone, Copy, PartialEq)]
+pub enum ClockMode {
+    Normal,
+    SimpleDelay,        // the first <delay> of every move is not counted
+    Bronstein,          // time spent is given back, but not more than <delay>
+    Hourglass           // time spent is given to opfor
+}
+
+#[derive(Clone, Copy)]
+pub struct Period {
+    pub moves:          i16,                // moves to be made in the period, 0 for the rest of the game
+    pub time:           u128,               // time given at the start of the period
+    pub inc:            u128                // time given after every move of the period
 }
 
 pub struct Clock {
     time:               u128,               // our time
     otim:               u128,               // opfor time
     time_control:       TimeControl,        // type of time control
-    mps:                i16,                // moves per base time increment (for TimeControl::Conventional)
-    bt:                 u128,               // base time (for TimeControl::Conventional)
-    inc:                u128,               // fixed time per move / increment
+    periods:            Vec<Period>,        // the last one repeats if it's not for the rest of the game
+    st:                 u128,               // fixed time per move (for TimeControl::Deadline)
+    mode:               ClockMode,
+    delay:              u128,               // for ClockMode::SimpleDelay and ClockMode::Bronstein
     pub updated:        bool
 }
 
@@ -24,10 +60,11 @@ impl Default for Clock {
         Self {
             time:           60000,
             otim:           60000,
-            time_control:   TimeControl::Incremental,
-            mps:            1,
-            bt:             60000,
-            inc:            0,
+            time_control:   TimeControl::Periods,
+            periods:        vec![Period { moves: 0, time: 60000, inc: 0 }],
+            st:             0,
+            mode:           ClockMode::Normal,
+            delay:          0,
             updated:        false
         }
     }
@@ -35,36 +72,55 @@ impl Default for Clock {
 
 impl Clock {
     // in fact, board.no is required
-    pub fn time_alloc(&mut self, halfmove_counter: i16, is_ponder_on: bool) -> u128 {
+    pub fn time_alloc(&self, halfmove_counter: i16, is_ponder_on: bool, overhead: u128) -> TimeBudget {
         let fullmove_counter = halfmove_counter / 2;
 
-        match self.time_control {
-            TimeControl::Conventional => {
-                let fraction = self.mps - fullmove_counter % self.mps;
-                let mut alloc = self.time / fraction as u128;
-                let fs = TPS[(fraction == 1) as usize][is_ponder_on as usize] + (alloc >> TDS[(fraction == 1) as usize][is_ponder_on as usize]);
-                if fs > alloc {
-                    return 1;
-                }
-                alloc -= fs;
-                alloc
-            },
-            TimeControl::Incremental => {
-                let divider = 70 - min(fullmove_counter, 10) * 2 - min(fullmove_counter, 20);
-                let alloc = self.time / divider as u128;
-                self.time -= alloc;
-                if self.time > self.inc {
-                    return alloc + ((self.inc * 15) >> 4);
-                }
-                // todo: fix when it's not 2 am
-                self.time += alloc;
-                self.time - 200
-            },
-            TimeControl::Deadline => {
-                let fs = TPS[1][is_ponder_on as usize] + (self.inc >> TDS[1][is_ponder_on as usize]);
-                self.inc - fs
+        if let TimeControl::Deadline = self.time_control {
+            let alloc = max(self.st.saturating_sub(overhead), 1);
+            return TimeBudget { optimum: alloc, maximum: alloc };
+        }
+
+        // moves to go and moves to keep the overhead aside for
+        let (period, mtg) = self.period(fullmove_counter);
+        let (mtg, safe) = if mtg != 0 {
+            (mtg as u128, mtg as u128)
+        } else {
+            ((70 - min(fullmove_counter, 10) * 2 - min(fullmove_counter, 20)) as u128, SAFE_MOVES)
+        };
+        let time = self.time.saturating_sub(overhead * safe);
+        // delay is free time on every move, but it can't be saved for later as an increment
+        let free = match self.mode {
+            ClockMode::SimpleDelay | ClockMode::Bronstein => self.delay.saturating_sub(overhead),
+            _ => 0
+        };
+
+        let mut optimum = time / mtg + ((period.inc * 3) >> 2);
+        if is_ponder_on {
+            optimum += optimum / PONDER_BONUS;
+        }
+        // leave at least half of the usual share for every other move before the control
+        let reserve = (mtg - 1) * (time / mtg) / 2;
+        let maximum = max(min(min(optimum * MAX_RATIO, time.saturating_sub(reserve)), time * MAX_SHARE / 100), 1);
+        TimeBudget {
+            optimum: min(optimum, maximum) + free,
+            maximum: maximum + free
+        }
+    }
+
+    // current period and moves left in it (0 if it's for the rest of the game)
+    pub fn period(&self, fullmove_counter: i16) -> (Period, i16) {
+        let mut moves = fullmove_counter;
+        for period in self.periods.iter() {
+            if period.moves == 0 {
+                return (*period, 0);
             }
+            if moves < period.moves {
+                return (*period, period.moves - moves);
+            }
+            moves -= period.moves;
         }
+        let last = *self.periods.last().unwrap();
+        (last, last.moves - moves % last.moves)
     }
 
     #[inline]
@@ -73,28 +129,32 @@ impl Clock {
             self.updated = false;
             return;
         }
+        let spent = match self.mode {
+            ClockMode::SimpleDelay | ClockMode::Bronstein => penalty.saturating_sub(self.delay),
+            _ => *penalty
+        };
+        let hourglass = self.mode == ClockMode::Hourglass;
         if playother {
-            self.otim -= *penalty;
+            self.otim = self.otim.saturating_sub(spent);
+            if hourglass {
+                self.time += spent;
+            }
         } else {
-            self.time -= *penalty;
+            self.time = self.time.saturating_sub(spent);
+            if hourglass {
+                self.otim += spent;
+            }
         }
     }
 
-    // ?
+    // how much better a draw looks for us because of the clock (in centipawns), positive if we are short on time
     pub fn is_it_time_for_draw(&self) -> i32 {
         match self.time_control {
-            TimeControl::Conventional => {
-                if self.time < 60000 || self.otim < 60000 {
-                    max(min((i32::try_from(self.otim).unwrap_or(120000) - i32::try_from(self.time).unwrap_or(120000)) / 100, 400), -400)
-                } else {
-                    -200
-                }
-            },
-            TimeControl::Incremental => {
+            TimeControl::Periods => {
                 if self.time < 60000 || self.otim < 60000 {
-                    max(min((i32::try_from(self.otim).unwrap_or(120000) - i32::try_from(self.time).unwrap_or(120000)) / 100, 400), -400)
+                    ((i32::try_from(self.otim).unwrap_or(120000) - i32::try_from(self.time).unwrap_or(120000)) / 500).clamp(-50, 50)
                 } else {
-                    -200
+                    0
                 }
             },
             TimeControl::Deadline => {
@@ -103,28 +163,63 @@ impl Clock {
         }
     }
 
-    /* Chess Engine Communication Protocol (XBoard) */
+    // returns false if there's no such option, so the caller may try other option sets
+    pub fn parse(&mut self, name: &str, value: &str) -> bool {
+        match name {
+            "TimeDelay" => {
+                self.delay = value.parse::<u128>().unwrap_or(0).clamp(0, 600) * 1000;
+            },
+            "TimeDelayMode" => {
+                self.mode = match value {
+                    "Simple"    => ClockMode::SimpleDelay,
+                    "Bronstein" => ClockMode::Bronstein,
+                    "Hourglass" => ClockMode::Hourglass,
+                    _           => ClockMode::Normal
+                };
+            },
+            _ => return false
+        }
+        true
+    }
 
-    pub fn level(&mut self, mps: &str, btr: &str, inc: &str) {
-        self.mps = mps.parse::<i16>().unwrap();
+    pub fn features(&self) {
+        println!("feature option=\"TimeDelay -spin {} 0 600\"", self.delay / 1000);
+        println!("feature option=\"TimeDelayMode -combo *None /// Simple /// Bronstein /// Hourglass\"");
+    }
 
-        let bts = btr.split(':').collect::<Vec<&str>>();
-        let mut bt = bts[0].parse::<u128>().unwrap() * 60 * 1000; 
-        if bts.len() > 1 {
-            bt += bts[1].parse::<u128>().unwrap() * 1000;
-        }
-        self.bt = bt;
+    // new game, but options stay
+    pub fn reset(&mut self) {
+        *self = Clock {
+            mode:   self.mode,
+            delay:  self.delay,
+            ..Clock::default()
+        };
+    }
+
+    /* Chess Engine Communication Protocol (XBoard) */
 
-        self.inc = inc.parse::<u128>().unwrap() * 1000;
+    // level MPS BASE INC [MPS BASE INC]... - every extra triple is the next period, e.g. "level 40 90 0 0 30 30"
+    pub fn level(&mut self, args: &[&str]) {
+        self.periods.clear();
+        for triple in args.chunks(3) {
+            let moves = triple[0].parse::<i16>().unwrap_or(0).max(0);
 
-        if self.mps == 0 {
-            self.time_control = TimeControl::Incremental;
-        } else {
-            self.time_control = TimeControl::Conventional;
+            let bts = triple[1].split(':').collect::<Vec<&str>>();
+            let mut time = bts[0].parse::<u128>().unwrap_or(0) * 60 * 1000;
+            if bts.len() > 1 {
+                time += bts[1].parse::<u128>().unwrap_or(0) * 1000;
+            }
+
+            let inc = (triple[2].parse::<f64>().unwrap_or(0.0) * 1000.0) as u128;
+            self.periods.push(Period { moves, time, inc });
+            if moves == 0 {
+                break;
+            }
         }
-        
-        self.time = bt;
-        self.otim = bt;
+        self.time_control = TimeControl::Periods;
+
+        self.time = self.periods[0].time;
+        self.otim = self.periods[0].time;
     }
 
     pub fn otim(&mut self, time: &str, from_update: bool) {
@@ -134,14 +229,81 @@ impl Clock {
 
     pub fn st(&mut self, time: &str) {
         self.time_control = TimeControl::Deadline;
-        self.inc = time.parse::<u128>().unwrap() * 1000;
+        self.st = time.parse::<u128>().unwrap() * 1000;
         
-        self.time = self.inc;
-        self.otim = self.inc;
+        self.time = self.st;
+        self.otim = self.st;
     }
 
     pub fn time(&mut self, time: &str, from_update: bool) {
         self.updated = from_update;
         self.time = time.parse::<u128>().unwrap() * 10;   
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // plays a whole game spending the maximum (or optimum) on every move and some GUI lag on top of it
+    fn simulate(clock: &mut Clock, moves: i16, lag: u128, worst: bool) {
+        let overhead = 50;
+        for no in (0..moves * 2).step_by(2) {
+            let budget = clock.time_alloc(no, false, overhead);
+            assert!(budget.optimum <= budget.maximum);
+            let spent = if worst { budget.maximum } else { budget.optimum } + lag;
+            assert!(spent < clock.time + clock.delay, "flag at move {}", no / 2 + 1);
+            clock.time_deduct(&spent, false);
+
+            let (period, mtg) = clock.period(no / 2);
+            clock.time += period.inc;
+            if mtg == 1 {
+                clock.time += clock.period(no / 2 + 1).0.time;
+            }
+        }
+    }
+
+    #[test]
+    fn test_clock_no_flag() {
+        let mut clock = Clock::default();
+        clock.level(&["40", "5", "0"]);
+        simulate(&mut clock, 150, 50, true);
+
+        let mut clock = Clock::default();
+        clock.level(&["0", "1", "0"]);
+        simulate(&mut clock, 200, 25, false);
+
+        let mut clock = Clock::default();
+        clock.level(&["0", "0:10", "1"]);
+        simulate(&mut clock, 150, 50, true);
+
+        let mut clock = Clock::default();
+        clock.level(&["40", "90", "0", "0", "30", "30"]);
+        simulate(&mut clock, 150, 50, true);
+
+        let mut clock = Clock::default();
+        clock.parse("TimeDelay", "2");
+        clock.parse("TimeDelayMode", "Bronstein");
+        clock.level(&["0", "0:30", "0"]);
+        simulate(&mut clock, 150, 50, true);
+    }
+
+    #[test]
+    fn test_clock_periods() {
+        let mut clock = Clock::default();
+        clock.level(&["40", "90", "0", "20", "60", "0", "0", "15", "30"]);
+        assert_eq!(clock.periods.len(), 3);
+        assert_eq!(clock.period(0).1, 40);
+        assert_eq!(clock.period(39).1, 1);
+        assert_eq!(clock.period(40).1, 20);
+        assert_eq!(clock.period(40).0.time, 3600000);
+        assert_eq!(clock.period(60).1, 0);
+        assert_eq!(clock.period(60).0.inc, 30000);
+
+        // the last period repeats
+        clock.level(&["40", "120", "0", "20", "60", "0"]);
+        assert_eq!(clock.period(59).1, 1);
+        assert_eq!(clock.period(60).1, 20);
+        assert_eq!(clock.period(85).1, 15);
+    }
+}
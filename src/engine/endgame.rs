@@ -0,0 +1,321 @@
+// Endgame recognizers: known material signatures get their own evaluation or a scale factor for the usual one.
+// Every recognizer sees the board from the strong side, values and scales are for that side.
+
+use std::{cmp::{max, min}, collections::HashMap, sync::OnceLock};
+use crate::frame::{util::*, board::Board};
+use super::weights::PIECE_WEIGHTS;
+
+pub const KNOWN_WIN:    i32 = 40000;
+pub const SCALE_NORMAL: i32 = 64;
+pub const SCALE_DRAW:   i32 = 0;
+
+pub enum Recognizer {
+    Value(fn(&Board, usize) -> i32),            // replaces eval(), positive is good for the strong side
+    Scale(fn(&Board, usize) -> i32)             // scales eval() when it favours the strong side
+}
+
+// strong side first, e.g. "KBNK" is king, bishop and knight against a bare king
+const RECOGNIZERS: [(&str, Recognizer); 5] = [
+    ("KBNK",  Recognizer::Value(kbnk)),
+    ("KRK",   Recognizer::Value(kxk)),
+    ("KQK",   Recognizer::Value(kxk)),
+    ("KPK",   Recognizer::Value(kpk)),
+    ("KRPKR", Recognizer::Scale(krpkr))
+];
+
+pub struct Endgames {
+    map: HashMap<u64, (&'static Recognizer, usize)>
+}
+
+impl Default for Endgames {
+    fn default() -> Self {
+        let mut map = HashMap::new();
+        for (code, recognizer) in RECOGNIZERS.iter() {
+            for strong in 0..2 {
+                map.insert(code_key(code, strong), (recognizer, strong));
+            }
+        }
+        Self { map }
+    }
+}
+
+impl Endgames {
+    // recognizer and the strong side for this material
+    #[inline]
+    pub fn probe(&self, key: u64) -> Option<(&'static Recognizer, usize)> {
+        self.map.get(&key).copied()
+    }
+}
+
+// 4 bits for the number of every piece but kings
+pub fn material_key(board: &Board) -> u64 {
+    let mut key = 0;
+    for piece in P..K {
+        key |= (board.bbs[piece].count_ones() as u64) << ((piece - P) * 4);
+    }
+    key
+}
+
+fn code_key(code: &str, strong: usize) -> u64 {
+    let weak_at = code[1..].find('K').unwrap() + 1;
+    let (ours, theirs) = code.split_at(weak_at);
+    let mut key = 0;
+    for (colour, side) in [(strong, ours), (strong ^ 1, theirs)] {
+        for c in side.chars().skip(1) {
+            let piece = match c {
+                'P' => P,
+                'N' => N,
+                'B' => B,
+                'R' => R,
+                _   => Q
+            } | colour;
+            key += 1 << ((piece - P) * 4);
+        }
+    }
+    key
+}
+
+// square as seen by the strong side (it always goes up the board)
+#[inline]
+fn rel(sq: usize, strong: usize) -> usize {
+    if strong == 1 { flip(sq) } else { sq }
+}
+
+// 0 in the center, 6 in a corner
+#[inline]
+fn edge(sq: usize) -> i32 {
+    (3 - min((sq & 7) as i32, 7 - (sq & 7) as i32)) + (3 - min((sq >> 3) as i32, 7 - (sq >> 3) as i32))
+}
+
+fn material(board: &Board, colour: usize) -> i32 {
+    (P..K).step_by(2).map(|piece| board.bbs[piece | colour].count_ones() as i32 * PIECE_WEIGHTS[1][(piece >> 1) - 1]).sum()
+}
+
+// mop-up: their king to the edge, ours close to it
+fn kxk(board: &Board, strong: usize) -> i32 {
+    let ours   = gtz(board.bbs[K | strong]);
+    let theirs = gtz(board.bbs[K | (strong ^ 1)]);
+    KNOWN_WIN + material(board, strong) + edge(theirs) * 80 + (7 - distance(ours, theirs) as i32) * 40
+}
+
+// mate is only possible in a corner of the bishop's colour
+fn kbnk(board: &Board, strong: usize) -> i32 {
+    let ours   = gtz(board.bbs[K | strong]);
+    let theirs = gtz(board.bbs[K | (strong ^ 1)]);
+    let mirror = if board.bbs[B | strong] & LIGHT_SQUARES != 0 { 7 } else { 0 };   // a1 and h8 are dark
+    let corner = 7 - min(distance(theirs ^ mirror, 0), distance(theirs ^ mirror, 63)) as i32;
+    KNOWN_WIN + material(board, strong) + corner * 80 + (7 - distance(ours, theirs) as i32) * 40
+}
+
+fn kpk(board: &Board, strong: usize) -> i32 {
+    let mut ours   = rel(gtz(board.bbs[K | strong]), strong);
+    let mut theirs = rel(gtz(board.bbs[K | (strong ^ 1)]), strong);
+    let mut pawn   = rel(gtz(board.bbs[P | strong]), strong);
+    if pawn & 7 > 3 {
+        ours ^= 7;
+        theirs ^= 7;
+        pawn ^= 7;
+    }
+    let stm = board.turn as usize ^ strong;
+    if kpk_table()[kpk_index(stm, ours, theirs, pawn)] == KPK_WIN {
+        KNOWN_WIN + PIECE_WEIGHTS[1][0] + (pawn >> 3) as i32 * 40
+    } else {
+        0
+    }
+}
+
+// Lucena is a win, the defending king in front of the pawn is a likely draw and Philidor is a draw
+fn krpkr(board: &Board, strong: usize) -> i32 {
+    let ours   = rel(gtz(board.bbs[K | strong]), strong);
+    let theirs = rel(gtz(board.bbs[K | (strong ^ 1)]), strong);
+    let pawn   = rel(gtz(board.bbs[P | strong]), strong);
+    let rook   = rel(gtz(board.bbs[R | (strong ^ 1)]), strong);
+    let (file, rank) = (pawn & 7, pawn >> 3);
+    let cut_off = (theirs & 7).abs_diff(file);
+    if rank == 6 && distance(ours, 56 | file) <= 1 && cut_off >= 2 {
+        return SCALE_NORMAL * 2;
+    }
+    if cut_off <= 1 && theirs >> 3 > rank {
+        if rank <= 4 && rook >> 3 == 5 && ours >> 3 < 5 {
+            return SCALE_DRAW;
+        }
+        return SCALE_NORMAL / 4;
+    }
+    SCALE_NORMAL
+}
+
+// scale for the material that has no recognizer: opposite coloured bishops, a wrong bishop with rook pawns and pawnless minors
+pub fn scale(board: &Board, strong: usize) -> i32 {
+    let weak = strong ^ 1;
+    let bbs = &board.bbs;
+    let majors = bbs[R] | bbs[R2] | bbs[Q] | bbs[Q2];
+    let bishops = bbs[B] | bbs[B2];
+    if bbs[B | strong].count_ones() == 1 && bbs[B | weak].count_ones() == 1 && (bishops & LIGHT_SQUARES).count_ones() == 1 {
+        if majors | bbs[N] | bbs[N2] != 0 {
+            return SCALE_NORMAL * 3 / 4;
+        }
+        let extra = bbs[P | strong].count_ones() as i32 - bbs[P | weak].count_ones() as i32;
+        return min(SCALE_NORMAL, 16 + 8 * max(extra, 0));
+    }
+    if majors | bbs[N | strong] == 0 && bbs[B | strong] != 0 && !board.has_non_pawn_material(weak == 1) && bbs[P | weak] == 0 {
+        let pawns = bbs[P | strong];
+        let file = if pawns & !FILE_A == 0 { FILE_A } else if pawns & !FILE_H == 0 { FILE_H } else { 0 };
+        if file != 0 {
+            let promotion = gtz(file & if strong == 0 { RANK_8 } else { RANK_1 });
+            let wrong = (bbs[B | strong] & LIGHT_SQUARES == 0) == (get_bit(LIGHT_SQUARES, promotion) != 0);
+            let on_one_colour = bbs[B | strong] & LIGHT_SQUARES == 0 || bbs[B | strong] & !LIGHT_SQUARES == 0;
+            if wrong && on_one_colour && distance(gtz(bbs[K | weak]), promotion) <= 1 {
+                return SCALE_DRAW;
+            }
+        }
+    }
+    if majors | bbs[P] | bbs[P2] == 0 {
+        let ours   = (bbs[N | strong] | bbs[B | strong]).count_ones();
+        let theirs = (bbs[N | weak] | bbs[B | weak]).count_ones();
+        if bbs[B | strong] == 0 && theirs == 0 && ours <= 2 {
+            return SCALE_DRAW;
+        }
+        if ours < theirs + 2 {
+            return SCALE_NORMAL / 8;
+        }
+    }
+    SCALE_NORMAL
+}
+
+/* KPK bitbase: white has the pawn on files a-d, built once by retrograde analysis */
+
+const KPK_SIZE:    usize = 2 * 64 * 64 * 24;
+const KPK_INVALID: u8 = 0;
+const KPK_UNKNOWN: u8 = 1;
+const KPK_DRAW:    u8 = 2;
+const KPK_WIN:     u8 = 4;
+
+static KPK: OnceLock<Vec<u8>> = OnceLock::new();
+
+#[inline]
+fn kpk_index(stm: usize, wk: usize, bk: usize, pawn: usize) -> usize {
+    stm | bk << 1 | wk << 7 | ((pawn & 7) + 4 * ((pawn >> 3) - 1)) << 13
+}
+
+fn king_steps(sq: usize) -> impl Iterator<Item = usize> {
+    let (file, rank) = ((sq & 7) as i32, (sq >> 3) as i32);
+    (-1..=1).flat_map(move |df| (-1..=1).map(move |dr| (file + df, rank + dr)))
+        .filter(move |&(f, r)| (f, r) != (file, rank) && (0..8).contains(&f) && (0..8).contains(&r))
+        .map(|(f, r)| (r * 8 + f) as usize)
+}
+
+#[inline]
+fn pawn_attacks(pawn: usize, sq: usize) -> bool {
+    sq >> 3 == (pawn >> 3) + 1 && (sq & 7).abs_diff(pawn & 7) == 1
+}
+
+fn kpk_table() -> &'static [u8] {
+    KPK.get_or_init(|| {
+        let mut table = vec![KPK_INVALID; KPK_SIZE];
+        let mut positions = Vec::with_capacity(KPK_SIZE);
+        for pawn in (8..56).filter(|sq| sq & 7 < 4) {
+            for wk in 0..64 {
+                for bk in 0..64 {
+                    for stm in 0..2 {
+                        positions.push((stm, wk, bk, pawn));
+                    }
+                }
+            }
+        }
+        for &(stm, wk, bk, pawn) in positions.iter() {
+            table[kpk_index(stm, wk, bk, pawn)] = kpk_initial(stm, wk, bk, pawn);
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &(stm, wk, bk, pawn) in positions.iter() {
+                let index = kpk_index(stm, wk, bk, pawn);
+                if table[index] == KPK_UNKNOWN {
+                    table[index] = kpk_classify(&table, stm, wk, bk, pawn);
+                    changed |= table[index] != KPK_UNKNOWN;
+                }
+            }
+        }
+        for result in table.iter_mut() {
+            if *result == KPK_UNKNOWN {
+                *result = KPK_DRAW;
+            }
+        }
+        table
+    })
+}
+
+fn kpk_initial(stm: usize, wk: usize, bk: usize, pawn: usize) -> u8 {
+    if distance(wk, bk) <= 1 || wk == pawn || bk == pawn || (stm == 0 && pawn_attacks(pawn, bk)) {
+        return KPK_INVALID;
+    }
+    let stop = pawn + 8;
+    // it promotes and the queen can't be taken
+    if stm == 0 && pawn >> 3 == 6 && wk != stop && bk != stop && (distance(bk, stop) > 1 || distance(wk, stop) == 1) {
+        return KPK_WIN;
+    }
+    // stalemate or the pawn is lost
+    if stm == 1 && (king_steps(bk).all(|sq| distance(sq, wk) <= 1 || pawn_attacks(pawn, sq)) || (distance(bk, pawn) == 1 && distance(wk, pawn) > 1)) {
+        return KPK_DRAW;
+    }
+    KPK_UNKNOWN
+}
+
+fn kpk_classify(table: &[u8], stm: usize, wk: usize, bk: usize, pawn: usize) -> u8 {
+    let mut results = 0;
+    if stm == 0 {
+        for sq in king_steps(wk) {
+            results |= table[kpk_index(1, sq, bk, pawn)];
+        }
+        if pawn >> 3 < 6 {
+            results |= table[kpk_index(1, wk, bk, pawn + 8)];
+            if pawn >> 3 == 1 && pawn + 8 != wk && pawn + 8 != bk {
+                results |= table[kpk_index(1, wk, bk, pawn + 16)];
+            }
+        }
+        if results & KPK_WIN != 0 { KPK_WIN } else if results & KPK_UNKNOWN != 0 { KPK_UNKNOWN } else { KPK_DRAW }
+    } else {
+        for sq in king_steps(bk) {
+            results |= table[kpk_index(0, wk, sq, pawn)];
+        }
+        if results & KPK_DRAW != 0 { KPK_DRAW } else if results & KPK_UNKNOWN != 0 { KPK_UNKNOWN } else { KPK_WIN }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_endgame_keys() {
+        let board = Board::import("8/8/4k3/8/8/2BNK3/8/8 w - - 0 1");
+        assert_eq!(material_key(&board), code_key("KBNK", 0));
+        let board = Board::import("8/4r3/4k3/8/1P6/2R1K3/8/8 b - - 0 1");
+        assert_eq!(material_key(&board), code_key("KRPKR", 0));
+        let board = Board::import("8/4R3/4k3/8/1p6/2r1K3/8/8 b - - 0 1");
+        assert_eq!(material_key(&board), code_key("KRPKR", 1));
+        let endgames = Endgames::default();
+        assert!(matches!(endgames.probe(code_key("KQK", 1)), Some((Recognizer::Value(_), 1))));
+        assert!(endgames.probe(code_key("KRRK", 0)).is_none());
+    }
+
+    #[test]
+    fn test_endgame_kpk() {
+        let positions = [
+            ("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1", true),       // a key square is taken
+            ("4k3/8/3K4/4P3/8/8/8/8 b - - 0 1", true),
+            ("4k3/4P3/4K3/8/8/8/8/8 b - - 0 1", false),      // stalemate
+            ("8/8/8/8/8/k7/P7/K7 w - - 0 1", false),         // rook pawns
+            ("k7/8/K7/P7/8/8/8/8 w - - 0 1", false),
+            ("8/8/3k4/8/8/8/P5K1/8 w - - 0 1", false),       // the square is caught
+            ("K7/8/8/8/8/2k5/5p2/8 w - - 0 1", true),
+            ("8/8/8/8/8/8/4k1pK/8 w - - 0 1", false)
+        ];
+        for (fen, win) in positions.into_iter() {
+            let board = Board::import(fen);
+            let strong = (board.bbs[P] == 0) as usize;
+            assert_eq!(kpk(&board, strong) > 0, win, "{}", fen);
+        }
+    }
+}
@@ -0,0 +1,111 @@
+// What GUI tells about the opponent and the playing environment (CECP name, rating, computer and ics).
+// Ambition is derived from it: positive means we play on for a win, negative - we'd take a draw.
+
+const AMBITION_CONTEMPT: i32 = 15;         // cp of contempt per point of ambition
+const AMBITION_GAP: i32 = 200;              // rating points per point of ambition
+
+#[derive(Default)]
+pub struct Opponent {
+    pub name:       String,
+    pub rating:     i32,                    // 0 if unknown
+    pub own_rating: i32,                    // 0 if unknown
+    pub computer:   bool,
+    pub ics:        String                  // empty if not playing on ICS
+}
+
+impl Opponent {
+    // returns false if it's not an opponent/environment command
+    pub fn parse(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (cmd, args) = line.split_once(' ').unwrap_or((line, ""));
+        match cmd {
+            "computer" => {
+                self.computer = true;
+            },
+            "ics" => {
+                self.ics = if args == "-" {
+                    String::new()
+                } else {
+                    args.to_string()
+                };
+            },
+            "name" => {
+                self.name = args.to_string();
+            },
+            "rating" => {
+                let ratings = args.split(' ').map(|rating| rating.parse::<i32>().unwrap_or(0)).collect::<Vec<i32>>();
+                self.own_rating = *ratings.first().unwrap_or(&0);
+                self.rating = *ratings.get(1).unwrap_or(&0);
+            },
+            _ => return false
+        }
+        self.log();
+        true
+    }
+
+    // -2 (much stronger engine) to 2 (much weaker human)
+    pub fn ambition(&self) -> i32 {
+        let mut ambition = 0;
+        if self.rating != 0 && self.own_rating != 0 {
+            ambition = ((self.own_rating - self.rating) / AMBITION_GAP).clamp(-2, 2);
+        }
+        // humans go wrong way more often, engines don't
+        if !self.computer && ambition > 0 {
+            ambition = (ambition + 1).min(2);
+        }
+        if self.computer && ambition < 0 {
+            ambition = (ambition - 1).max(-2);
+        }
+        ambition
+    }
+
+    #[inline]
+    pub fn contempt(&self) -> i32 {
+        self.ambition() * AMBITION_CONTEMPT
+    }
+
+    // ics stays for the next game, everything else is sent by GUI again
+    pub fn reset(&mut self) {
+        *self = Opponent {
+            ics: std::mem::take(&mut self.ics),
+            ..Opponent::default()
+        };
+    }
+
+    pub fn log(&self) {
+        println!("#DEBUG\tOpponent: \"{}\", rating {} (own {}), {}, {}, ambition {}",
+            self.name,
+            self.rating,
+            self.own_rating,
+            if self.computer { "computer" } else { "human" },
+            if self.ics.is_empty() { "local" } else { &self.ics },
+            self.ambition()
+        );
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opponent() {
+        let mut opponent = Opponent::default();
+        assert!(opponent.parse("name Some Player"));
+        assert!(opponent.parse("rating 2100 1650"));
+        assert!(opponent.parse("ics freechess.org"));
+        assert!(!opponent.parse("usermove e2e4"));
+        assert_eq!(opponent.name, "Some Player");
+        assert_eq!((opponent.own_rating, opponent.rating), (2100, 1650));
+        assert_eq!(opponent.ambition(), 2);
+
+        opponent.reset();
+        assert_eq!(opponent.ics, "freechess.org");
+        assert_eq!(opponent.ambition(), 0);
+        opponent.parse("computer");
+        opponent.parse("rating 2100 2500");
+        assert_eq!(opponent.ambition(), -2);
+        assert!(opponent.contempt() < 0);
+    }
+}
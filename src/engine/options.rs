@@ -1,8 +1,14 @@
-use std::cmp::{max, min};
+use super::personality::Personality;
 
 pub struct Options {
-    pub rand:        i32,
-    pub rand_status: bool
+    pub rand:             i32,
+    pub rand_status:      bool,
+    pub multipv:          usize,
+    pub move_overhead:    u128,     // ms kept aside on every move for the GUI/network lag
+    pub contempt:         i32,      // cp, a draw is worth -contempt for the root side (and +contempt for opfor)
+    pub contempt_dynamic: bool,     // scale contempt by root eval and game phase
+    pub personality:      Personality,
+    pub weights_file:     String    // custom weights, empty for the built-in ones
     // pub memory:  usize
 }
 
@@ -10,7 +16,13 @@ impl Default for Options {
     fn default() -> Options {
         Self {
             rand: 20,
-            rand_status: false
+            rand_status: false,
+            multipv: 1,
+            move_overhead: 50,
+            contempt: 10,
+            contempt_dynamic: false,
+            personality: Personality::default(),
+            weights_file: String::new()
         }
     } 
 }
@@ -25,9 +37,33 @@ impl Options {
         match cmd[0] {
             "Random" => {
                 let mut rand = cmd[1].parse::<i32>().unwrap_or(5);
-                rand = min(max(rand, 0), 50);
+                rand = rand.clamp(0, 50);
                 self.rand = rand * 4;
             },
+            "MultiPV" => {
+                let multipv = cmd[1].parse::<usize>().unwrap_or(1);
+                self.multipv = multipv.clamp(1, 64);
+            },
+            "MoveOverhead" => {
+                let move_overhead = cmd[1].parse::<u128>().unwrap_or(50);
+                self.move_overhead = move_overhead.clamp(0, 5000);
+            },
+            "Contempt" => {
+                let contempt = cmd[1].parse::<i32>().unwrap_or(0);
+                self.contempt = contempt.clamp(-200, 200);
+            },
+            "DynamicContempt" => {
+                self.contempt_dynamic = cmd[1] == "1" || cmd[1] == "true";
+            },
+            "Personality" => {
+                match Personality::preset(cmd[1]) {
+                    Some(personality) => self.personality = personality,
+                    None => println!("Error (unknown personality): {}", cmd[1])
+                }
+            },
+            "WeightsFile" => {
+                self.weights_file = cmd[1].to_string();
+            },
             _ => {
                 println!("Error (unknown option): {}", query);
             }
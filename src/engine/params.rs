@@ -0,0 +1,92 @@
+// Search parameters, kept apart from Weights so they can be tuned without touching eval().
+// Every parameter is exposed as a CECP spin option with the same name.
+
+pub struct Params {
+    pub nmp_min_depth:      i16,        // null move is not tried below this depth
+    pub nmp_base:           i16,        // base null move reduction (on top of the usual 1 ply)
+    pub nmp_depth_div:      i16,        // +1 reduction per this many plies of depth
+    pub nmp_eval_div:       i32,        // +1 reduction per this much static eval above beta
+    pub nmp_eval_max:       i16,        // cap for the eval-based part of the reduction
+    pub nmp_verify_depth:   i16,        // null move cutoffs are verified starting from this depth
+    pub lmr_min_depth:      i16,        // late moves are not reduced below this depth
+    pub lmr_min_moves:      usize,      // ...and not before this many moves were searched
+    pub lmr_base:           i32,        // reduction = base + ln(depth) * ln(move number) / div (both in 1/100 of a ply)
+    pub lmr_div:            i32,
+    pub lmr_history_div:    i32,        // -1 reduction per this much history score (and +1 for negative history)
+    pub lazy_margin:        i32,        // quiescence stands pat on material and squares alone this far outside the window (0 - off)
+    pub risk:               i32,        // % of the table reductions, set by the personality
+    pub lmr_table:         [[i16; 64]; 64]  // [depth][move number], rebuilt by init_lmr()
+}
+
+impl Default for Params {
+    fn default() -> Params {
+        let mut params = Self {
+            nmp_min_depth:      3,
+            nmp_base:           2,
+            nmp_depth_div:      4,
+            nmp_eval_div:       800,
+            nmp_eval_max:       3,
+            nmp_verify_depth:   12,
+            lmr_min_depth:      3,
+            lmr_min_moves:      2,
+            lmr_base:           75,
+            lmr_div:            225,
+            lmr_history_div:    8192,
+            lazy_margin:        2000,
+            risk:               100,
+            lmr_table:          [[0; 64]; 64]
+        };
+        params.init_lmr();
+        params
+    }
+}
+
+impl Params {
+    // returns false if there's no such parameter, so the caller may try other option sets
+    pub fn parse(&mut self, name: &str, value: &str) -> bool {
+        let Ok(value) = value.parse::<i32>() else {
+            return false;
+        };
+        match name {
+            "NullMoveMinDepth"    => self.nmp_min_depth    = value.clamp(1, 20) as i16,
+            "NullMoveBase"        => self.nmp_base         = value.clamp(0, 6) as i16,
+            "NullMoveDepthDiv"    => self.nmp_depth_div    = value.clamp(1, 20) as i16,
+            "NullMoveEvalDiv"     => self.nmp_eval_div     = value.clamp(1, 4000),
+            "NullMoveEvalMax"     => self.nmp_eval_max     = value.clamp(0, 6) as i16,
+            "NullMoveVerifyDepth" => self.nmp_verify_depth = value.clamp(1, 64) as i16,
+            "LmrMinDepth"         => self.lmr_min_depth    = value.clamp(1, 20) as i16,
+            "LmrMinMoves"         => self.lmr_min_moves    = value.clamp(1, 64) as usize,
+            "LmrBase"             => self.lmr_base         = value.clamp(0, 300),
+            "LmrDiv"              => self.lmr_div          = value.clamp(50, 1000),
+            "LmrHistoryDiv"       => self.lmr_history_div  = value.clamp(256, 65536),
+            "LazyEvalMargin"      => self.lazy_margin      = value.clamp(0, 8000),
+            _ => return false
+        }
+        self.init_lmr();
+        true
+    }
+
+    pub fn init_lmr(&mut self) {
+        for (depth, row) in self.lmr_table.iter_mut().enumerate().skip(1) {
+            for (num, reduction) in row.iter_mut().enumerate().skip(1) {
+                let r = self.lmr_base as f32 / 100.0 + (depth as f32).ln() * (num as f32).ln() * 100.0 / self.lmr_div as f32;
+                *reduction = (r * self.risk as f32 / 100.0).round() as i16;
+            }
+        }
+    }
+
+    pub fn features(&self) {
+        println!("feature option=\"NullMoveMinDepth -spin {} 1 20\"", self.nmp_min_depth);
+        println!("feature option=\"NullMoveBase -spin {} 0 6\"", self.nmp_base);
+        println!("feature option=\"NullMoveDepthDiv -spin {} 1 20\"", self.nmp_depth_div);
+        println!("feature option=\"NullMoveEvalDiv -spin {} 1 4000\"", self.nmp_eval_div);
+        println!("feature option=\"NullMoveEvalMax -spin {} 0 6\"", self.nmp_eval_max);
+        println!("feature option=\"NullMoveVerifyDepth -spin {} 1 64\"", self.nmp_verify_depth);
+        println!("feature option=\"LmrMinDepth -spin {} 1 20\"", self.lmr_min_depth);
+        println!("feature option=\"LmrMinMoves -spin {} 1 64\"", self.lmr_min_moves);
+        println!("feature option=\"LmrBase -spin {} 0 300\"", self.lmr_base);
+        println!("feature option=\"LmrDiv -spin {} 50 1000\"", self.lmr_div);
+        println!("feature option=\"LmrHistoryDiv -spin {} 256 65536\"", self.lmr_history_div);
+        println!("feature option=\"LazyEvalMargin -spin {} 0 8000\"", self.lazy_margin);
+    }
+}
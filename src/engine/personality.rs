@@ -0,0 +1,58 @@
+// Playing styles: groups of Weights are scaled (in %) on top of the defaults or a custom weights file,
+// plus a few search traits. Selected by the Personality option.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Personality {
+    pub name:           &'static str,
+    pub material:       i32,            // piece values and profitable attacks
+    pub pawns:          i32,            // pawn structure and passers
+    pub positional:     i32,            // outposts, open files and center control
+    pub king_attack:    i32,            // attacks near the enemy king
+    pub mobility:       i32,
+    pub contempt:       i32,            // cp on top of the Contempt option
+    pub risk:           i32             // % of the usual late move reductions
+}
+
+pub const PERSONALITIES: [Personality; 5] = [
+    Personality { name: "Default",    material: 100, pawns: 100, positional: 100, king_attack: 100, mobility: 100, contempt:   0, risk: 100 },
+    Personality { name: "Aggressive", material:  90, pawns:  80, positional: 100, king_attack: 150, mobility: 125, contempt:  20, risk: 115 },
+    Personality { name: "Positional", material: 100, pawns: 120, positional: 140, king_attack:  90, mobility: 110, contempt:   0, risk: 100 },
+    Personality { name: "Solid",      material: 110, pawns: 130, positional: 110, king_attack:  80, mobility: 100, contempt: -10, risk:  85 },
+    Personality { name: "Material",   material: 125, pawns: 100, positional:  85, king_attack:  90, mobility:  90, contempt:  10, risk: 100 }
+];
+
+impl Default for Personality {
+    fn default() -> Personality {
+        PERSONALITIES[0]
+    }
+}
+
+impl Personality {
+    pub fn preset(name: &str) -> Option<Self> {
+        PERSONALITIES.iter().find(|p| p.name.eq_ignore_ascii_case(name)).copied()
+    }
+
+    // for the CECP combo option, e.g. "*Default /// Aggressive /// ..."
+    pub fn combo(&self) -> String {
+        PERSONALITIES.iter().map(|p| {
+            if p.name == self.name {
+                format!("*{}", p.name)
+            } else {
+                p.name.to_string()
+            }
+        }).collect::<Vec<String>>().join(" /// ")
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_personality_preset() {
+        assert_eq!(Personality::preset("aggressive").unwrap().name, "Aggressive");
+        assert!(Personality::preset("reckless").is_none());
+        assert_eq!(Personality::default().combo(), "*Default /// Aggressive /// Positional /// Solid /// Material");
+    }
+}
@@ -0,0 +1,136 @@
+// When to resign, offer a draw or accept one. Scores are in centipawns from our side.
+// Every threshold is exposed as a CECP spin option with the same name.
+
+pub struct Policy {
+    pub resign_score:   i32,        // resign if the score is at or below -resign_score... (0 = never resign)
+    pub resign_moves:   u16,        // ...for this many moves in a row
+    pub draw_score:     i32,        // offer a draw if the score stays within +-draw_score...
+    pub draw_moves:     u16,        // ...for this many moves in a row
+    pub draw_min_move:  i16,        // no draw offers before this move number
+    pub ambition:       i32,        // from what we know about opfor, see Opponent::ambition()
+    resign_count:       u16,
+    draw_count:         u16
+}
+
+impl Default for Policy {
+    fn default() -> Policy {
+        Self {
+            resign_score:   1000,
+            resign_moves:   5,
+            draw_score:     15,
+            draw_moves:     10,
+            draw_min_move:  40,
+            ambition:       0,
+            resign_count:   0,
+            draw_count:     0
+        }
+    }
+}
+
+impl Policy {
+    // both counters are updated once per our move, so this must be called right after every think() for us
+    pub fn on_move(&mut self, score: i32) {
+        if self.resign_score != 0 && score <= -self.resign_score {
+            self.resign_count += 1;
+        } else {
+            self.resign_count = 0;
+        }
+        if score.abs() <= self.draw_score {
+            self.draw_count += 1;
+        } else {
+            self.draw_count = 0;
+        }
+    }
+
+    // being ambitious, we hold on longer
+    #[inline]
+    pub fn should_resign(&self) -> bool {
+        self.resign_score != 0 && self.resign_count >= self.resign_moves * (1 + self.ambition.max(0) as u16)
+    }
+
+    // draw_value: what a draw is worth for us (it's negative with contempt)
+    // time_bias: positive if we are short on time, so a draw looks better
+    pub fn should_offer_draw(&mut self, score: i32, draw_value: i32, time_bias: i32, fullmove: i16) -> bool {
+        let min_move = self.draw_min_move + 20 * self.ambition.max(0) as i16;
+        if fullmove < min_move || self.draw_count < self.draw_moves || score - time_bias > draw_value {
+            return false;
+        }
+        // don't repeat the offer every move
+        self.draw_count = 0;
+        true
+    }
+
+    // the game is no better for us than the draw (within the draw margin, which is wider if we are cautious)
+    #[inline]
+    pub fn should_accept_draw(&self, score: i32, draw_value: i32, time_bias: i32) -> bool {
+        score - time_bias <= draw_value + self.draw_score * (1 - self.ambition.min(0))
+    }
+
+    pub fn reset(&mut self) {
+        self.resign_count = 0;
+        self.draw_count = 0;
+    }
+
+    // returns false if there's no such option, so the caller may try other option sets
+    pub fn parse(&mut self, name: &str, value: &str) -> bool {
+        let Ok(value) = value.parse::<i32>() else {
+            return false;
+        };
+        match name {
+            "ResignScore"  => self.resign_score  = value.clamp(0, 10000),
+            "ResignMoves"  => self.resign_moves  = value.clamp(1, 100) as u16,
+            "DrawScore"    => self.draw_score    = value.clamp(0, 500),
+            "DrawMoves"    => self.draw_moves    = value.clamp(1, 100) as u16,
+            "DrawMinMove"  => self.draw_min_move = value.clamp(0, 500) as i16,
+            _ => return false
+        }
+        true
+    }
+
+    pub fn features(&self) {
+        println!("feature option=\"ResignScore -spin {} 0 10000\"", self.resign_score);
+        println!("feature option=\"ResignMoves -spin {} 1 100\"", self.resign_moves);
+        println!("feature option=\"DrawScore -spin {} 0 500\"", self.draw_score);
+        println!("feature option=\"DrawMoves -spin {} 1 100\"", self.draw_moves);
+        println!("feature option=\"DrawMinMove -spin {} 0 500\"", self.draw_min_move);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy() {
+        let mut policy = Policy::default();
+        for _ in 0..policy.resign_moves - 1 {
+            policy.on_move(-1200);
+        }
+        assert!(!policy.should_resign());
+        policy.on_move(-1200);
+        assert!(policy.should_resign());
+        policy.on_move(-200);
+        assert!(!policy.should_resign());
+
+        for _ in 0..policy.draw_moves {
+            policy.on_move(-5);
+        }
+        assert!(!policy.should_offer_draw(-5, 0, 0, policy.draw_min_move - 1));
+        assert!(!policy.should_offer_draw(-5, -50, 0, policy.draw_min_move));
+        assert!( policy.should_offer_draw(-5, 0, 0, policy.draw_min_move));
+        assert!(!policy.should_offer_draw(-5, 0, 0, policy.draw_min_move));
+
+        assert!( policy.should_accept_draw(10, 0, 0));
+        assert!(!policy.should_accept_draw(10, -50, 0));
+        assert!( policy.should_accept_draw(60, 0, 50));
+
+        policy.ambition = -1;
+        assert!( policy.should_accept_draw(25, 0, 0));
+        policy.ambition = 1;
+        for _ in 0..policy.resign_moves {
+            policy.on_move(-1200);
+        }
+        assert!(!policy.should_resign());
+    }
+}
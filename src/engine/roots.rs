@@ -0,0 +1,114 @@
+// Root moves of the current think() with their stats across iterations.
+// The first MultiPV moves are the reported lines (ordered by score), the rest is ordered by nodes spent.
+
+use std::cmp::Reverse;
+use crate::frame::util::*;
+
+#[derive(Clone)]
+pub struct RootMove {
+    pub mov:        u32,
+    pub score:      i32,        // -INF if it's not among the best lines (or not searched yet)
+    pub prev_score: i32,        // score from the previous iteration
+    pub depth:      i16,        // depth of the score
+    pub nodes:      u64,        // nodes spent on this move in the current iteration
+    pub pv:         Vec<u32>    // starts with the move itself
+}
+
+impl RootMove {
+    pub fn new(mov: u32) -> Self {
+        Self {
+            mov,
+            score:      -INF,
+            prev_score: -INF,
+            depth:      0,
+            nodes:      0,
+            pv:         vec![mov]
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct RootMoves {
+    pub moves: Vec<RootMove>
+}
+
+impl RootMoves {
+    pub fn new(legals: &[u32]) -> Self {
+        let mut legals = legals.to_vec();
+        legals.sort();
+        legals.reverse();
+        Self {
+            moves: legals.into_iter().map(RootMove::new).collect()
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.moves.is_empty()
+    }
+
+    pub fn begin_iteration(&mut self) {
+        for rm in self.moves.iter_mut() {
+            rm.prev_score = rm.score;
+            rm.nodes = 0;
+        }
+    }
+
+    // the line is searched starting with the move that was the best among the remaining ones last time
+    pub fn pick_line(&mut self, pv_idx: usize) {
+        let mut best = pv_idx;
+        for (i, rm) in self.moves.iter().enumerate().skip(pv_idx) {
+            if rm.prev_score > self.moves[best].prev_score {
+                best = i;
+            }
+        }
+        self.moves[pv_idx..=best].rotate_right(1);
+    }
+
+    // a new best move goes to the head of its line right away, so an aborted iteration still has it first
+    #[inline]
+    pub fn promote(&mut self, pv_idx: usize, i: usize) {
+        self.moves[pv_idx..=i].rotate_right(1);
+    }
+
+    pub fn sort_lines(&mut self, pv_idx: usize) {
+        self.moves[pv_idx..].sort_by_key(|rm| Reverse(rm.score));
+    }
+
+    pub fn reorder(&mut self, lines: usize) {
+        self.moves[lines..].sort_by_key(|rm| Reverse(rm.nodes));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roots_ordering() {
+        let mut roots = RootMoves::new(&[1, 2, 3, 4, 5]);
+        assert_eq!(roots.moves.iter().map(|rm| rm.mov).collect::<Vec<u32>>(), [5, 4, 3, 2, 1]);
+
+        roots.promote(0, 3);
+        assert_eq!(roots.moves.iter().map(|rm| rm.mov).collect::<Vec<u32>>(), [2, 5, 4, 3, 1]);
+
+        for (rm, (score, nodes)) in roots.moves.iter_mut().zip([(40, 100), (-INF, 10), (60, 20), (-INF, 300), (-INF, 50)]) {
+            rm.score = score;
+            rm.nodes = nodes;
+        }
+        roots.sort_lines(0);
+        roots.reorder(2);
+        assert_eq!(roots.moves.iter().map(|rm| rm.mov).collect::<Vec<u32>>(), [4, 2, 3, 1, 5]);
+
+        roots.begin_iteration();
+        roots.pick_line(1);
+        assert_eq!(roots.moves[1].mov, 2);
+        assert!(roots.moves.iter().all(|rm| rm.nodes == 0));
+    }
+}
@@ -0,0 +1,119 @@
+// Strength limiting: everything is derived from a single Elo setting through a level in [0, 20].
+// Weaker levels see less (depth, nodes, quiescence) and sometimes pick a worse line out of several best ones.
+// Both settings are exposed as CECP options (LimitStrength and Elo).
+
+const ELO_MIN: i32 = 800;
+const ELO_MAX: i32 = 2400;
+const ELO_PER_LEVEL: i32 = (ELO_MAX - ELO_MIN) / 20;
+const LINES: usize = 4;                     // lines to choose from
+
+pub struct Skill {
+    pub limit:  bool,
+    pub elo:    i32
+}
+
+impl Default for Skill {
+    fn default() -> Skill {
+        Self {
+            limit:  false,
+            elo:    1600
+        }
+    }
+}
+
+impl Skill {
+    #[inline]
+    pub fn level(&self) -> i32 {
+        ((self.elo - ELO_MIN) / ELO_PER_LEVEL).clamp(0, 20)
+    }
+
+    pub fn depth(&self) -> i16 {
+        1 + (self.level() * 3 / 4) as i16
+    }
+
+    pub fn nodes(&self) -> u64 {
+        200 << (self.level() / 2)
+    }
+
+    pub fn lines(&self) -> usize {
+        LINES
+    }
+
+    // plies of quiescence search after the nominal depth, and whether quiet checks are seen there
+    pub fn qs_plies(&self) -> usize {
+        1 + self.level() as usize / 2
+    }
+
+    #[inline]
+    pub fn qs_checks(&self) -> bool {
+        self.level() >= 12
+    }
+
+    // cp of score loss for which a line is e times less likely to be picked: ~10 at the top level, ~100 at the bottom
+    pub fn temperature(&self) -> f64 {
+        10.0 * 2.0_f64.powf((20 - self.level()) as f64 / 6.0)
+    }
+
+    // scores in cp of the best lines (best first), u is uniform in [0, 1)
+    // the chance of every line is exp(-loss / temperature), normalized
+    pub fn pick(&self, scores: &[i32], u: f64) -> usize {
+        let Some(best) = scores.first() else {
+            return 0;
+        };
+        let t = self.temperature();
+        let weights = scores.iter().map(|score| (-((best - score) as f64) / t).exp()).collect::<Vec<f64>>();
+        let mut target = u * weights.iter().sum::<f64>();
+        for (i, weight) in weights.iter().enumerate() {
+            if target < *weight {
+                return i;
+            }
+            target -= weight;
+        }
+        scores.len() - 1
+    }
+
+    // returns false if there's no such option, so the caller may try other option sets
+    pub fn parse(&mut self, name: &str, value: &str) -> bool {
+        match name {
+            "LimitStrength" => {
+                self.limit = value == "1" || value == "true";
+            },
+            "Elo" => {
+                self.elo = value.parse::<i32>().unwrap_or(1600).clamp(ELO_MIN, ELO_MAX);
+            },
+            _ => return false
+        }
+        true
+    }
+
+    pub fn features(&self) {
+        println!("feature option=\"LimitStrength -check {}\"", self.limit as i32);
+        println!("feature option=\"Elo -spin {} {} {}\"", self.elo, ELO_MIN, ELO_MAX);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skill() {
+        let mut skill = Skill::default();
+        skill.parse("Elo", "3000");
+        assert_eq!(skill.level(), 20);
+        assert_eq!(skill.pick(&[50, 20, 10, -300], 0.5), 0);
+        skill.parse("Elo", "0");
+        assert_eq!(skill.level(), 0);
+        assert!(skill.depth() < 3 && skill.qs_plies() == 1 && !skill.qs_checks());
+
+        // the chance of every line falls with its loss, and a much worse line is practically never picked
+        let scores = [50, 20, 10, -500];
+        let mut picked = [0; 4];
+        for i in 0..1000 {
+            picked[skill.pick(&scores, i as f64 / 1000.0)] += 1;
+        }
+        assert!(picked[0] > picked[1] && picked[1] > picked[2] && picked[2] > picked[3]);
+        assert!(picked[1] > 100 && picked[3] < 10);
+    }
+}
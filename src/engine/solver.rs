@@ -0,0 +1,225 @@
+// Mate solver for chess problems: does the side to move force mate in N moves, with which keys and how fast.
+// Every key is tried at 1, 2 .. N moves, so the shortest mate it has is proven as well (nothing shorter exists).
+// More than one key within N moves means the problem is cooked.
+// Both the limit and the checks-only pruning are exposed: "mate N" command, MateChecksOnly CECP option, "ace mate N FEN" in the shell.
+
+use std::time::Instant;
+use crate::frame::{util::*, board::Board};
+
+const NODES_BETWEEN_STOPS: u64 = 0b111111111111;
+
+pub struct Solution {
+    pub key:    u32,
+    pub depth:  usize,          // mates in this many moves and not less
+    pub line:   Vec<u32>        // main line: the longest defence and the first mate against it
+}
+
+#[derive(Default)]
+pub struct MateReport {
+    pub solutions:  Vec<Solution>,      // shortest first
+    pub nodes:      u64,
+    pub ms:         u128,
+    pub stopped:    bool                // keys that were not proven yet are missing
+}
+
+#[derive(Default)]
+pub struct Solver {
+    pub checks_only:    bool,           // every move of the attacker is a check (it's unsound for quiet keys and threats)
+    nodes:              u64,
+    stopped:            bool
+}
+
+impl Solver {
+    pub fn parse(&mut self, name: &str, value: &str) -> bool {
+        match name {
+            "MateChecksOnly" => {
+                self.checks_only = value == "1" || value == "true";
+            },
+            _ => return false
+        }
+        true
+    }
+
+    pub fn features(&self) {
+        println!("feature option=\"MateChecksOnly -check {}\"", self.checks_only as i32);
+    }
+
+    // stop() is asked every few thousand nodes, true aborts the search
+    pub fn solve(&mut self, board: &mut Board, limit: usize, stop: &mut dyn FnMut() -> bool) -> MateReport {
+        let ts = Instant::now();
+        self.nodes = 0;
+        self.stopped = false;
+        let mut solutions = Vec::new();
+        for key in self.attacks(board, limit) {
+            board.make_move(key);
+            let depth = (1..=limit).find(|&depth| self.defend(board, depth, stop));
+            board.revert_move();
+            if self.stopped {
+                break;
+            }
+            if let Some(depth) = depth {
+                let line = self.line(board, key, depth, stop);
+                solutions.push(Solution { key, depth, line });
+            }
+        }
+        solutions.sort_by_key(|solution| solution.depth);
+        MateReport {
+            solutions,
+            nodes:      self.nodes,
+            ms:         ts.elapsed().as_millis(),
+            stopped:    self.stopped
+        }
+    }
+
+    // the attacker (to move) mates in n moves or less
+    fn attack(&mut self, board: &mut Board, n: usize, stop: &mut dyn FnMut() -> bool) -> bool {
+        for mov in self.attacks(board, n) {
+            board.make_move(mov);
+            let mate = self.defend(board, n, stop);
+            board.revert_move();
+            if mate || self.stopped {
+                return mate;
+            }
+        }
+        false
+    }
+
+    // the defender (to move) is mated now or can't avoid mate in n - 1 more moves of the attacker
+    fn defend(&mut self, board: &mut Board, n: usize, stop: &mut dyn FnMut() -> bool) -> bool {
+        self.nodes += 1;
+        if self.nodes & NODES_BETWEEN_STOPS == 0 && stop() {
+            self.stopped = true;
+        }
+        if self.stopped {
+            return false;
+        }
+        let moves = board.get_legal_moves();
+        if moves.is_empty() {
+            return board.is_in_check();
+        }
+        if n == 1 {
+            return false;
+        }
+        for mov in moves {
+            board.make_move(mov);
+            let mate = self.attack(board, n - 1, stop);
+            board.revert_move();
+            if !mate {
+                return false;
+            }
+        }
+        !self.stopped
+    }
+
+    // moves of the attacker worth trying: only checks can mate at once, checks and captures go first
+    fn attacks(&mut self, board: &mut Board, n: usize) -> Vec<u32> {
+        let mut checks = Vec::new();
+        let mut rest = Vec::new();
+        let mut moves = board.get_legal_moves();
+        moves.sort();
+        moves.reverse();
+        for mov in moves {
+            board.make_move(mov);
+            let check = board.is_in_check();
+            board.revert_move();
+            if check {
+                checks.push(mov);
+            } else if n > 1 && !self.checks_only {
+                rest.push(mov);
+            }
+        }
+        checks.extend(rest);
+        checks
+    }
+
+    fn line(&mut self, board: &mut Board, key: u32, depth: usize, stop: &mut dyn FnMut() -> bool) -> Vec<u32> {
+        let mut line = vec![key];
+        board.make_move(key);
+        let mut n = depth;
+        loop {
+            // the defence that holds out the longest
+            let mut defence = None;
+            for mov in board.get_legal_moves() {
+                board.make_move(mov);
+                let left = (1..n).find(|&left| self.attack(board, left, stop)).unwrap_or(n - 1);
+                board.revert_move();
+                if defence.is_none_or(|(_, longest)| left > longest) {
+                    defence = Some((mov, left));
+                }
+            }
+            let Some((mov, left)) = defence else {
+                break;
+            };
+            line.push(mov);
+            board.make_move(mov);
+            n = left;
+            let Some(mov) = self.attacks(board, n).into_iter().find(|&mov| {
+                board.make_move(mov);
+                let mate = self.defend(board, n, stop);
+                board.revert_move();
+                mate
+            }) else {
+                break;
+            };
+            line.push(mov);
+            board.make_move(mov);
+        }
+        for _ in 0..line.len() {
+            board.revert_move();
+        }
+        line
+    }
+}
+
+impl MateReport {
+    // the attacker is the side to move of the solved position
+    pub fn print(&self, limit: usize, turn: bool, checks_only: bool) {
+        let by = if checks_only { " by checks" } else { "" };
+        match self.solutions.first() {
+            None if self.stopped => println!("#DEBUG\tMate search stopped: no mate in {}{} found so far", limit, by),
+            None => println!("#DEBUG\tNo mate in {}{}", limit, by),
+            Some(shortest) => {
+                // quiet moves of the attacker and the keys left after a stop may still be shorter
+                let proof = if checks_only || self.stopped { "" } else { ", none is shorter" };
+                println!("#DEBUG\tShortest mate{}: in {}{}", by, shortest.depth, proof);
+                for solution in self.solutions.iter() {
+                    let line = solution.line.iter().enumerate().map(|(i, mov)| move_transform(*mov, turn ^ (i & 1 != 0))).collect::<Vec<String>>();
+                    println!("#DEBUG\tKey {} mates in {}: {}", move_transform(solution.key, turn), solution.depth, line.join(" "));
+                }
+                match self.solutions.len() {
+                    1 => println!("#DEBUG\tSolutions: 1, sound"),
+                    count => println!("#DEBUG\tSolutions: {}, cooked ({} extra keys)", count, count - 1)
+                }
+            }
+        }
+        println!("#DEBUG\tMate search: {} nodes, {} ms{}", self.nodes, self.ms, if self.stopped { ", stopped" } else { "" });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solve(fen: &str, limit: usize, checks_only: bool) -> Vec<(String, usize)> {
+        let mut board = Board::import(fen);
+        let mut solver = Solver { checks_only, ..Default::default() };
+        let report = solver.solve(&mut board, limit, &mut || false);
+        assert_eq!(board.export(), fen);
+        report.solutions.iter().map(|solution| (move_transform(solution.key, board.turn), solution.depth)).collect()
+    }
+
+    #[test]
+    fn test_solver() {
+        // back rank: two rooks are two keys
+        assert_eq!(solve("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 1, false), vec![("a1a8".to_string(), 1)]);
+        assert_eq!(solve("6k1/5ppp/8/8/8/8/8/RR4K1 w - - 0 1", 1, false).len(), 2);
+        // Morphy: a quiet key, no mate in 1 and none by checks only
+        let morphy = "kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1";
+        assert!(solve(morphy, 1, false).is_empty());
+        assert_eq!(solve(morphy, 2, false), vec![("a1a6".to_string(), 2)]);
+        assert!(solve(morphy, 2, true).is_empty());
+        // stalemate is no mate
+        assert!(solve("k7/8/1K6/8/8/8/8/2Q5 w - - 0 1", 1, false).iter().all(|(key, _)| key != "c1c7"));
+    }
+}
@@ -0,0 +1,64 @@
+// Per-term breakdown of eval() for the eval command, filled by the same code the search uses.
+// Values are in internal units from white's view (weights are already colour-signed).
+
+#[derive(Clone, Copy)]
+pub enum Term {
+    Material,           // piece values and squares (heatmap)
+    Pawns,              // doubled, isolated, phalanga, blocked center pawns
+    Outposts,
+    RookFiles,          // rooks and queens on or attacking (semi)open files
+    KingAttack,
+    Shelter,            // pawn shelter and storm in front of the king
+    Threats,            // profitable attacks
+    Pins,
+    Mobility,
+    Passed,             // passers, their paths and king proximity, rooks behind them
+    Center,             // central control and space
+    King,               // king mobility as a queen and opposition
+    Pieces,             // bishop pair, queen and knight, material imbalance, placement
+    Endgame,            // recognized material or the scaling of the rest
+    Random,
+    Tempo               // side to move bonus and scaling
+}
+
+const TERMS: usize = 16;
+const NAMES: [&str; TERMS] = ["Material/PST", "Pawn structure", "Outposts", "Rook files", "King attack", "King shelter", "Threats", "Pins", "Mobility", "Passed pawns", "Center", "King", "Pieces", "Endgame", "Random", "Tempo"];
+
+#[derive(Default)]
+pub struct Trace {
+    pub terms:      [[[i32; 2]; 2]; TERMS],     // [term][colour][phase]: opening, endgame
+    pub phase:      f32,                        // weight of the opening part
+    pub tapered:    i32,                        // white's view before tempo
+    pub total:      i32                         // white's view, eval() is the same from the side to move
+}
+
+impl Trace {
+    #[inline]
+    pub fn add(&mut self, term: Term, colour: usize, mg: i32, eg: i32) {
+        let values = &mut self.terms[term as usize][colour];
+        values[0] += mg;
+        values[1] += eg;
+    }
+
+    // terms that are not mirrored in the trace of the colour flipped position
+    pub fn asymmetric_terms(&self, flipped: &Trace) -> Vec<&'static str> {
+        NAMES.iter().zip(self.terms.iter().zip(flipped.terms.iter())).filter(|(_, (values, flipped))| {
+            (0..2).any(|phase| values[0][phase] != -flipped[1][phase] || values[1][phase] != -flipped[0][phase])
+        }).map(|(name, _)| *name).collect()
+    }
+
+    pub fn print(&self) {
+        println!("#DEBUG\t{:<16}|{:>16}|{:>16}|{:>16}|{:>8}", "Term", "White (mg eg)", "Black (mg eg)", "Total (mg eg)", "Tapered");
+        for (name, values) in NAMES.iter().zip(self.terms.iter()) {
+            let total = [values[0][0] + values[1][0], values[0][1] + values[1][1]];
+            println!("#DEBUG\t{:<16}|{:>8}{:>8}|{:>8}{:>8}|{:>8}{:>8}|{:>8.1}",
+                name,
+                values[0][0], values[0][1],
+                values[1][0], values[1][1],
+                total[0], total[1],
+                total[0] as f32 * self.phase + total[1] as f32 * (1.0 - self.phase)
+            );
+        }
+        println!("#DEBUG\tPhase: {:.3} (opening), tapered: {}, total: {} ({} cp, white's view)", self.phase, self.tapered, self.total, self.total / 4);
+    }
+}
@@ -1,4 +1,15 @@
+use std::fs;
 use crate::frame::util::*;
+use super::personality::Personality;
+
+pub const KING_DANGER_UNITS: usize = 100;     // size of the king danger table, more attack units are capped
+pub const IMBALANCE_DIV: i32 = 16;            // imbalance tables are in 1/16 of score units
+
+// pure material per [phase][piece], it's a part of the heatmap
+pub const PIECE_WEIGHTS: [[i32; 6]; 2] = [
+    [ 328, 1348, 1460, 1908, 4100, 0 ],
+    [ 396, 1124, 1188, 2048, 3744, 0 ]
+];
 
 pub struct Weights {
     /* These weights are stored with respect to the colour, black pieces will provide negative values
@@ -20,27 +31,61 @@ pub struct Weights {
     pub p_semiblocked: 	  [i32;  2],			// pawn blocked on starting square by enemy pieces, use for C/F files
     pub p_blocked:		  [i32;  2],			// pawn blocked on starting square by anything, use for D/E files
     pub p_passing:		 [[i32;  8];  2],		// small additional bonus per passing pawn
+    pub p_backward:		  [i32;  2],			// pawn can't be supported by neighbours and its stop square is attacked by a pawn
+    pub p_candidate:	 [[i32;  8];  2],		// not passed yet, but nothing in front and enough supporters to get through
+    pub p_passer_protected: [i32; 2],			// passed pawn protected by a pawn
+    pub p_passer_connected: [i32; 2],			// passed pawn with a passed neighbour
+    pub p_passer_free:	 [[i32;  2];  2],		// per relative rank of passed pawn with nothing on its way (phased)
+    pub p_unstoppable:	 [[i32;  2];  2],		// passed pawn the enemy king can't catch in a pawn endgame, the fastest one only (phased)
     pub nb_outpost:		  [i32;  2],			// knight/bishop stays on outpost sq
     pub nb_outpost_reach: [i32;  2],			// knight/bishop may reach an outpost sq easily
     pub rq_open:		  [i32;  2],			// rook/queen on open file (will apply with atk_open!)
     pub rq_semiopen:	  [i32;  2],			// rook/queen on semiopen file (will apply with atk_semiopen!)
     pub rq_atk_open:	  [i32;  2],			// rook/queen attacks any open file
     pub rq_atk_semiopen:  [i32;  2],			// rook/queen attacks any semiopen file
+    pub r_seventh:		 [[i32;  2];  2],		// rook on the 7th rank with the enemy king on the 8th (phased)
+    pub r_behind_passer: [[i32;  2];  2],		// rook behind a passed pawn of any colour on its file (phased)
+    pub r_trapped:		 [[i32;  2];  2],		// rook with few moves shut in by its own king that can't castle that way, double without castling at all (phased)
+    pub b_bad:			 [[i32;  2];  2],		// per own pawn on the bishop's colour, times 1 + our blocked pawns on c-f files (phased)
+    pub b_trapped:		 [[i32;  2];  2],		// bishop on a7/h7 shut in by a pawn on b6/g6 (phased)
+    pub q_early:		 [[i32;  2];  2],		// per own minor piece still at home when the queen has left it (phased)
+    pub s_space:		 [[i32;  2];  2],		// per safe square on our side of c-f files, the ones behind our pawns count twice (phased)
     pub k_opposition:	 [[i32;  2];  2],		// king has opposition (phased)
     pub k_mobility_as_q: [[i32;  2];  2],	    // king security (phased)
     pub k_pawn_dist1:    [[i32;  2];  2],		// bonus if near passing pawn (phased)
     pub k_pawn_dist2:    [[i32;  2];  2],		// bonus if near passing pawn (phased)
-    pub g_atk_pro:		  [i32;  2],			// per profitable attack (lazy check for pawns)
+    pub k_passer_dist:   [[i32;  2];  2],		// per rank * (2 * enemy king - own king distance to the stop square) of passed pawn (phased)
+    pub k_shelter:		 [[i32;  8];  2],		// by relative rank (0-7) of the nearest own pawn on the king file or next to it, [0] if there's none
+    pub k_storm:		 [[i32;  8];  2],		// by relative rank of the nearest unblocked enemy pawn there, [0] if there's none
+    pub k_storm_blocked:  [i32;  2],			// enemy pawn there is stopped right in front of our pawn
+    pub k_open_file:	  [i32;  2],			// no pawns at all on the king file or next to it
+    pub k_atk_units:	   [i32;  5],			// king safety units per attack on the enemy king zone by [p, n, b, r, q]
+    pub k_check_units:	   [i32;  4],			// king safety units per safe check by [n, b, r, q]
+    pub k_weak_units:	    i32,				// king safety units per attacked zone square defended by the king only (or not at all)
+    pub k_danger_mul:	    i32,				// king danger is units^2 * mul / 16...
+    pub k_danger_max:	    i32,				// ...but not more than this
+    pub k_danger:		 [[i32; KING_DANGER_UNITS]; 2], // [colour][attack units], built from the three above
     pub g_atk_pro_pinned: [i32;  2],			// per profitable attack on pinned piece (lazy check for pawns)
-    pub g_atk_pro_double: [i32;  2],			// per double profitable attack (e.g. knight fork!)
     pub g_atk_center:	 [[i32;  2];  2],		// positional bonus per attack on a center square (not like with pawns!) (phased)
-    pub g_atk_near_king: [[i32;  5];  2],		// [p, n, b, r, q] attacks intersect with enemy king atk map (lazy check for pawns)
     pub g_atk_ppt:		  [i32;  2],			// per attack on (any colour) passed pawn trajectory
     pub g_ppawn_block:	  [i32;  2],			// passing pawn blocked
-    pub g_atk_pro_ppb:	  [i32;  2],			// per profitable attack on passing pawn blocker
-    pub s_mobility:		   i32,					// per every square (for N, B, R, Q)
+    pub g_threat_minor:	 [[i32;  5];  2],		// per enemy [p, n, b, r, q] attacked by a knight/bishop, pawns only if they're weak
+    pub g_threat_rook:	 [[i32;  5];  2],		// same for a rook, weak ones only
+    pub g_threat_king:	  [i32;  2],			// king attacks a weak enemy
+    pub g_hanging:		  [i32;  2],			// per weak enemy that is not defended at all
+    pub g_threat_safe_pawn: [i32; 2],			// per enemy piece attacked by a pawn that's safe
+    pub g_threat_push:	  [i32;  2],			// per enemy piece a safe pawn push would attack
+    pub g_restricted:	  [i32;  2],			// per square both sides attack, and the enemy doesn't hold with a pawn
+    pub g_knight_on_queen: [i32; 2],			// safe knight square to attack the enemy queen
+    pub g_slider_on_queen: [i32; 2],			// safe bishop/rook square to attack the enemy queen
+    pub s_mob_knight:	[[[i32;  9]; 2]; 2],	// by the number of safe squares a piece attacks (phased), safe are the ones not attacked by enemy pawns,
+    pub s_mob_bishop:	[[[i32; 14]; 2]; 2],	// not taken by our king or our pawns that can't move
+    pub s_mob_rook:		[[[i32; 15]; 2]; 2],
+    pub s_mob_queen:	[[[i32; 28]; 2]; 2],
     pub s_bishop_pair:	  [i32;  2],			// bishop pair smol bonus
     pub s_qnight:		  [i32;  2],			// queen & knight smol bonus
+    pub m_imbalance_ours:  [[i32; 6]; 6],		// quadratic material imbalance by [piece][piece below or same] of ours and...
+    pub m_imbalance_theirs: [[i32; 6]; 6],		// ...of theirs, pieces are [bishop pair, p, n, b, r, q]; not colour-signed, black's sum is subtracted
     pub s_turn:			  [i32;  2],
     pub s_turn_div:	       i32,					// score +/-= score / div
     pub rand:			   i32					// random weight of [-rand, +rand] will be added to an evaluated leaf
@@ -50,11 +95,6 @@ impl Weights {
     // possible modify by some multipliers
     pub fn init() -> Self {
 
-        let pieces_weights_const = [
-            [ 328, 1348, 1460, 1908, 4100, 0 ],
-            [ 396, 1124, 1188, 2048, 3744, 0 ]
-        ];
-
         let p_isolated_pre = -44;
         let p_doubled_pre = -52;
         let p_phalanga_pre = 80;
@@ -64,27 +104,88 @@ impl Weights {
         let p_semiblocked_pre = -200;
         let p_blocked_pre = -200;
         let p_passing_pre = [0, 120, 140, 160, 190, 240, 300, 0];
+        let p_backward_pre = -40;
+        let p_candidate_pre = [0, 20, 20, 40, 60, 80, 0, 0];
+        let p_passer_protected_pre = 40;
+        let p_passer_connected_pre = 60;
+        let p_passer_free_pre = [8, 24];
+        let p_unstoppable_pre = [0, 2000];
         let nb_outpost_pre = 80;
         let nb_outpost_reach_pre = 80;
         let rq_atk_open_pre = 40;
         let rq_atk_semiopen_pre = 20;
         let rq_open_pre = 100;
         let rq_semiopen_pre = 80;
+        let r_seventh_pre = [44, 88];
+        let r_behind_passer_pre = [20, 60];
+        let r_trapped_pre = [-104, -20];
+        let b_bad_pre = [-6, -14];
+        let b_trapped_pre = [-300, -300];
+        let q_early_pre = [-24, 0];
+        let s_space_pre = [8, 0];
         let k_opposition_pre = [0, 60];
         let k_mobility_as_q_pre = [-4, 0]; // second is always 0
         let k_pawn_dist1_pre = [0, 140];
         let k_pawn_dist2_pre = [0, 60];
-        let g_atk_pro_pre = 42;
+        let k_passer_dist_pre = [0, 4];
+        let k_shelter_pre = [-80, 80, 50, 10, -10, -20, -20, 0];
+        let k_storm_pre = [0, -20, -120, -60, -20, 0, 0, 0];
+        let k_storm_blocked_pre = -40;
+        let k_open_file_pre = -60;
+        let k_atk_units = [1, 2, 2, 3, 5];
+        let k_check_units = [8, 5, 8, 6];
+        let k_weak_units = 3;
+        let k_danger_mul = 16;
+        let k_danger_max = 3200;
         let g_atk_pro_pinned_pre = 710;
-        let g_atk_pro_double_pre = 840;
         let g_atk_center_pre = [40, 0];
-        let g_atk_near_king_pre = [ 40, 32, 40, 24, 16 ];
         let g_atk_ppt_pre = 20;
         let g_ppawn_block_pre = 40;
-        let g_atk_pro_ppb_pre = 40;
-        let s_mobility = 7;
+        // a weak enemy is attacked by us and not protected by a pawn
+        let g_threat_minor_pre = [36, 98, 132, 206, 240];
+        let g_threat_rook_pre = [48, 104, 102, 38, 98];
+        let g_threat_king_pre = 112;
+        let g_hanging_pre = 104;
+        let g_threat_safe_pawn_pre = 266;
+        let g_threat_push_pre = 86;
+        let g_restricted_pre = 14;
+        let g_knight_on_queen_pre = 26;
+        let g_slider_on_queen_pre = 76;
+        let s_mob_knight_pre = [
+            [-124, -106, -24,  -8,   6,  26,  44,  56,  66],
+            [-162, -112, -62, -32,  10,  22,  34,  40,  50]
+        ];
+        let s_mob_bishop_pre = [
+            [-96, -40,  32,  52,  76, 102, 110, 126, 126, 136, 162, 162, 182, 196],
+            [-118, -46, -6,  26,  48,  84, 108, 114, 130, 146, 156, 172, 176, 194]
+        ];
+        let s_mob_rook_pre = [
+            [-120, -40,  4,  6,   6,  22,  44,  62,  80,  80,  82,  96, 114, 114, 124],
+            [-156, -34, 46, 78, 140, 198, 206, 242, 268, 278, 316, 328, 336, 338, 344]
+        ];
+        let s_mob_queen_pre = [
+            [-60, -24, -16, -18, 40,  46,  46,  70,  76, 106, 128, 130, 130, 132, 134, 134, 144, 144, 154, 158, 186, 216, 216, 216, 220, 228, 228, 232],
+            [-96, -60, -14,  38, 80, 110, 118, 150, 156, 192, 192, 200, 242, 254, 262, 266, 272, 282, 294, 300, 302, 336, 336, 342, 364, 364, 384, 438]
+        ];
         let s_bishop_pair_pre = 80;
         let s_qnight_pre = 40;
+        // the pair alone is s_bishop_pair
+        let m_imbalance_ours = [
+            [   0,   0,   0,   0,    0,  0],
+            [  40,  38,   0,   0,    0,  0],
+            [  32, 255, -62,   0,    0,  0],
+            [   0, 104,   4,   0,    0,  0],
+            [ -26,  -2,  47, 105, -208,  0],
+            [-189,  24, 117, 133, -134, -6]
+        ];
+        let m_imbalance_theirs = [
+            [   0,   0,   0,   0,   0,  0],
+            [  36,   0,   0,   0,   0,  0],
+            [   9,  63,   0,   0,   0,  0],
+            [  59,  65,  42,   0,   0,  0],
+            [  46,  39,  24, -24,   0,  0],
+            [  97, 100, -42, 137, 268,  0]
+        ];
         let s_turn_pre = 35;
         let s_turn_div = 12;
 
@@ -251,8 +352,8 @@ impl Weights {
         for i in 0..2 {
             for j in 0..6 {
                 for k in 0..64 {
-                    heatmap[i][(j << 1) + 2][k] =  pesto[i][j][flip(k)] * 3 / 2 + pieces_weights_const[i][j];
-                    heatmap[i][(j << 1) + 3][k] = -pesto[i][j][k      ] * 3 / 2 - pieces_weights_const[i][j];
+                    heatmap[i][(j << 1) + 2][k] =  pesto[i][j][flip(k)] * 3 / 2 + PIECE_WEIGHTS[i][j];
+                    heatmap[i][(j << 1) + 3][k] = -pesto[i][j][k      ] * 3 / 2 - PIECE_WEIGHTS[i][j];
                 }
             }
         }
@@ -273,22 +374,7 @@ impl Weights {
             }
         }
 
-        /* Transform other W */
-    
-        let mut g_atk_near_king: [[i32; 5]; 2] = [g_atk_near_king_pre, g_atk_near_king_pre];
-        for i in 0..5 {
-            g_atk_near_king[1][i] = -g_atk_near_king[1][i];
-        }
-
-        let mut p_passing: [[i32; 8]; 2] = [p_passing_pre, p_passing_pre];
-        for i in 0..4 {
-            p_passing[1].swap(i, 7 - i);
-        }
-        for i in 0..8 {
-            p_passing[1][i] = -p_passing[1][i];
-        }
-
-        Self {
+        let mut w = Self {
             heatmap,
             p_isolated: colour_transform(p_isolated_pre),
             p_doubled: colour_transform(p_doubled_pre),
@@ -298,35 +384,373 @@ impl Weights {
             p_outpost_block: colour_transform(p_outpost_block_pre),
             p_semiblocked: colour_transform(p_semiblocked_pre),
             p_blocked: colour_transform(p_blocked_pre),
-            p_passing,
+            p_passing: rank_transform(p_passing_pre),
+            p_backward: colour_transform(p_backward_pre),
+            p_candidate: rank_transform(p_candidate_pre),
+            p_passer_protected: colour_transform(p_passer_protected_pre),
+            p_passer_connected: colour_transform(p_passer_connected_pre),
+            p_passer_free: [colour_transform(p_passer_free_pre[0]), colour_transform(p_passer_free_pre[1])],
+            p_unstoppable: [colour_transform(p_unstoppable_pre[0]), colour_transform(p_unstoppable_pre[1])],
             nb_outpost: colour_transform(nb_outpost_pre),
             nb_outpost_reach: colour_transform(nb_outpost_reach_pre),
             rq_open: colour_transform(rq_open_pre),
             rq_semiopen: colour_transform(rq_semiopen_pre),
             rq_atk_open: colour_transform(rq_atk_open_pre),
             rq_atk_semiopen: colour_transform(rq_atk_semiopen_pre),
+            r_seventh: [colour_transform(r_seventh_pre[0]), colour_transform(r_seventh_pre[1])],
+            r_behind_passer: [colour_transform(r_behind_passer_pre[0]), colour_transform(r_behind_passer_pre[1])],
+            r_trapped: [colour_transform(r_trapped_pre[0]), colour_transform(r_trapped_pre[1])],
+            b_bad: [colour_transform(b_bad_pre[0]), colour_transform(b_bad_pre[1])],
+            b_trapped: [colour_transform(b_trapped_pre[0]), colour_transform(b_trapped_pre[1])],
+            q_early: [colour_transform(q_early_pre[0]), colour_transform(q_early_pre[1])],
+            s_space: [colour_transform(s_space_pre[0]), colour_transform(s_space_pre[1])],
             k_opposition: [colour_transform(k_opposition_pre[0]), colour_transform(k_opposition_pre[1])],
             k_mobility_as_q: [colour_transform(k_mobility_as_q_pre[0]), colour_transform(k_mobility_as_q_pre[1])],
             k_pawn_dist1: [colour_transform(k_pawn_dist1_pre[0]), colour_transform(k_pawn_dist1_pre[1])],
             k_pawn_dist2: [colour_transform(k_pawn_dist2_pre[0]), colour_transform(k_pawn_dist2_pre[1])],
-            g_atk_pro: colour_transform(g_atk_pro_pre),
+            k_passer_dist: [colour_transform(k_passer_dist_pre[0]), colour_transform(k_passer_dist_pre[1])],
+            k_shelter: [k_shelter_pre, k_shelter_pre.map(|weight| -weight)],
+            k_storm: [k_storm_pre, k_storm_pre.map(|weight| -weight)],
+            k_storm_blocked: colour_transform(k_storm_blocked_pre),
+            k_open_file: colour_transform(k_open_file_pre),
+            k_atk_units,
+            k_check_units,
+            k_weak_units,
+            k_danger_mul,
+            k_danger_max,
+            k_danger: [[0; KING_DANGER_UNITS]; 2],
             g_atk_pro_pinned: colour_transform(g_atk_pro_pinned_pre),
-            g_atk_pro_double: colour_transform(g_atk_pro_double_pre),
             g_atk_center: [colour_transform(g_atk_center_pre[0]), colour_transform(g_atk_center_pre[1])],
-            g_atk_near_king,
             g_atk_ppt: colour_transform(g_atk_ppt_pre),
             g_ppawn_block: colour_transform(g_ppawn_block_pre),
-            g_atk_pro_ppb: colour_transform(g_atk_pro_ppb_pre),
-            s_mobility,
+            g_threat_minor: [g_threat_minor_pre, g_threat_minor_pre.map(|weight| -weight)],
+            g_threat_rook: [g_threat_rook_pre, g_threat_rook_pre.map(|weight| -weight)],
+            g_threat_king: colour_transform(g_threat_king_pre),
+            g_hanging: colour_transform(g_hanging_pre),
+            g_threat_safe_pawn: colour_transform(g_threat_safe_pawn_pre),
+            g_threat_push: colour_transform(g_threat_push_pre),
+            g_restricted: colour_transform(g_restricted_pre),
+            g_knight_on_queen: colour_transform(g_knight_on_queen_pre),
+            g_slider_on_queen: colour_transform(g_slider_on_queen_pre),
+            s_mob_knight: mobility_transform(s_mob_knight_pre),
+            s_mob_bishop: mobility_transform(s_mob_bishop_pre),
+            s_mob_rook: mobility_transform(s_mob_rook_pre),
+            s_mob_queen: mobility_transform(s_mob_queen_pre),
             s_bishop_pair: colour_transform(s_bishop_pair_pre),
             s_qnight: colour_transform(s_qnight_pre),
+            m_imbalance_ours,
+            m_imbalance_theirs,
             s_turn: colour_transform(s_turn_pre),
             s_turn_div,
             rand: 0
+        };
+        w.init_king_danger();
+        w
+    }
+
+    // quadratic, so a few pieces attacking together are worth a lot more than each of them alone
+    pub fn init_king_danger(&mut self) {
+        for units in 0..KING_DANGER_UNITS {
+            let danger = (units as i32 * units as i32 * self.k_danger_mul / 16).min(self.k_danger_max);
+            self.k_danger[0][units] =  danger;
+            self.k_danger[1][units] = -danger;
+        }
+    }
+
+    // [colour] of [bishop pair, p, n, b, r, q] counts -> colour-signed imbalance of both sides
+    pub fn imbalance(&self, counts: &[[i32; 6]; 2]) -> [i32; 2] {
+        let mut imbalance = [0; 2];
+        for ally in 0..2 {
+            let enemy = (ally == 0) as usize;
+            for (i, count) in counts[ally].iter().enumerate().filter(|(_, count)| **count != 0) {
+                let value = (0..=i).map(|j| self.m_imbalance_ours[i][j] * counts[ally][j] + self.m_imbalance_theirs[i][j] * counts[enemy][j]).sum::<i32>();
+                imbalance[ally] += count * value;
+            }
         }
+        [imbalance[0] / IMBALANCE_DIV, -imbalance[1] / IMBALANCE_DIV]
+    }
+
+    /* Custom weights and personalities */
+
+    // colour-signed weights by the name of their field, as written in a weights file
+    fn scalar_mut(&mut self, name: &str) -> Option<&mut [i32; 2]> {
+        Some(match name {
+            "p_isolated"       => &mut self.p_isolated,
+            "p_doubled"        => &mut self.p_doubled,
+            "p_phalanga"       => &mut self.p_phalanga,
+            "p_atk_center"     => &mut self.p_atk_center,
+            "p_outpost"        => &mut self.p_outpost,
+            "p_outpost_block"  => &mut self.p_outpost_block,
+            "p_semiblocked"    => &mut self.p_semiblocked,
+            "p_blocked"        => &mut self.p_blocked,
+            "p_backward"       => &mut self.p_backward,
+            "p_passer_protected" => &mut self.p_passer_protected,
+            "p_passer_connected" => &mut self.p_passer_connected,
+            "nb_outpost"       => &mut self.nb_outpost,
+            "nb_outpost_reach" => &mut self.nb_outpost_reach,
+            "rq_open"          => &mut self.rq_open,
+            "rq_semiopen"      => &mut self.rq_semiopen,
+            "rq_atk_open"      => &mut self.rq_atk_open,
+            "rq_atk_semiopen"  => &mut self.rq_atk_semiopen,
+            "k_storm_blocked"  => &mut self.k_storm_blocked,
+            "k_open_file"      => &mut self.k_open_file,
+            "g_atk_pro_pinned" => &mut self.g_atk_pro_pinned,
+            "g_atk_ppt"        => &mut self.g_atk_ppt,
+            "g_ppawn_block"    => &mut self.g_ppawn_block,
+            "g_threat_king"    => &mut self.g_threat_king,
+            "g_hanging"        => &mut self.g_hanging,
+            "g_threat_safe_pawn" => &mut self.g_threat_safe_pawn,
+            "g_threat_push"    => &mut self.g_threat_push,
+            "g_restricted"     => &mut self.g_restricted,
+            "g_knight_on_queen" => &mut self.g_knight_on_queen,
+            "g_slider_on_queen" => &mut self.g_slider_on_queen,
+            "s_bishop_pair"    => &mut self.s_bishop_pair,
+            "s_qnight"         => &mut self.s_qnight,
+            "s_turn"           => &mut self.s_turn,
+            _ => return None
+        })
+    }
+
+    // same, but [phase][colour]
+    fn phased_mut(&mut self, name: &str) -> Option<&mut [[i32; 2]; 2]> {
+        Some(match name {
+            "k_opposition"     => &mut self.k_opposition,
+            "k_mobility_as_q"  => &mut self.k_mobility_as_q,
+            "k_pawn_dist1"     => &mut self.k_pawn_dist1,
+            "k_pawn_dist2"     => &mut self.k_pawn_dist2,
+            "k_passer_dist"    => &mut self.k_passer_dist,
+            "p_passer_free"    => &mut self.p_passer_free,
+            "p_unstoppable"    => &mut self.p_unstoppable,
+            "g_atk_center"     => &mut self.g_atk_center,
+            "r_seventh"        => &mut self.r_seventh,
+            "r_behind_passer"  => &mut self.r_behind_passer,
+            "r_trapped"        => &mut self.r_trapped,
+            "b_bad"            => &mut self.b_bad,
+            "b_trapped"        => &mut self.b_trapped,
+            "q_early"          => &mut self.q_early,
+            "s_space"          => &mut self.s_space,
+            _ => return None
+        })
+    }
+
+    // values are for white, like the *_pre ones in init(); returns false for an unknown name or wrong amount of values
+    pub fn set(&mut self, name: &str, values: &[i32]) -> bool {
+        if let Some(weight) = self.scalar_mut(name) {
+            if values.len() != 1 {
+                return false;
+            }
+            *weight = colour_transform(values[0]);
+            return true;
+        }
+        if let Some(weight) = self.phased_mut(name) {
+            if values.len() != 2 {
+                return false;
+            }
+            *weight = [colour_transform(values[0]), colour_transform(values[1])];
+            return true;
+        }
+        match (name, values.len()) {
+            ("s_mob_knight", 18) => self.s_mob_knight = mobility_transform([values[..9].try_into().unwrap(), values[9..].try_into().unwrap()]),
+            ("s_mob_bishop", 28) => self.s_mob_bishop = mobility_transform([values[..14].try_into().unwrap(), values[14..].try_into().unwrap()]),
+            ("s_mob_rook", 30) => self.s_mob_rook = mobility_transform([values[..15].try_into().unwrap(), values[15..].try_into().unwrap()]),
+            ("s_mob_queen", 56) => self.s_mob_queen = mobility_transform([values[..28].try_into().unwrap(), values[28..].try_into().unwrap()]),
+            ("s_turn_div", 1) => self.s_turn_div = values[0].max(1),
+            ("k_shelter", 8) | ("k_storm", 8) => {
+                let weights = if name == "k_shelter" { &mut self.k_shelter } else { &mut self.k_storm };
+                for (i, value) in values.iter().enumerate() {
+                    weights[0][i] =  *value;
+                    weights[1][i] = -*value;
+                }
+            },
+            ("g_threat_minor", 5) | ("g_threat_rook", 5) => {
+                let weights = if name == "g_threat_minor" { &mut self.g_threat_minor } else { &mut self.g_threat_rook };
+                for (i, value) in values.iter().enumerate() {
+                    weights[0][i] =  *value;
+                    weights[1][i] = -*value;
+                }
+            },
+            ("k_atk_units", 5) => self.k_atk_units.copy_from_slice(values),
+            ("k_check_units", 4) => self.k_check_units.copy_from_slice(values),
+            ("k_weak_units", 1) => self.k_weak_units = values[0],
+            ("k_danger_mul", 1) => {
+                self.k_danger_mul = values[0];
+                self.init_king_danger();
+            },
+            ("k_danger_max", 1) => {
+                self.k_danger_max = values[0];
+                self.init_king_danger();
+            },
+            ("p_passing", 8) => self.p_passing = rank_transform(values.try_into().unwrap()),
+            ("p_candidate", 8) => self.p_candidate = rank_transform(values.try_into().unwrap()),
+            ("m_imbalance_ours", 21) | ("m_imbalance_theirs", 21) => {
+                // lower triangle, row by row
+                let table = if name == "m_imbalance_ours" { &mut self.m_imbalance_ours } else { &mut self.m_imbalance_theirs };
+                let mut values = values.iter();
+                for (i, row) in table.iter_mut().enumerate() {
+                    for weight in row.iter_mut().take(i + 1) {
+                        *weight = *values.next().unwrap();
+                    }
+                }
+            },
+            _ => return false
+        }
+        true
+    }
+
+    // a weights file is lines of "name value..." (e.g. "k_opposition 0 60"), '#' starts a comment
+    pub fn load(&mut self, path: &str) -> Result<(), String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        for (no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let name = words.next().unwrap_or("");
+            let values = words.map(|word| word.parse::<i32>()).collect::<Result<Vec<i32>, _>>()
+                .map_err(|_| format!("{}:{}: bad value", path, no + 1))?;
+            if !self.set(name, &values) {
+                return Err(format!("{}:{}: unknown weight or wrong number of values: {}", path, no + 1, name));
+            }
+        }
+        Ok(())
+    }
+
+    // scales groups of weights in %, so it goes on top of whatever weights are loaded
+    pub fn apply_personality(&mut self, personality: &Personality) {
+        fn scale(weights: &mut [i32], pct: i32) {
+            for weight in weights.iter_mut() {
+                *weight = *weight * pct / 100;
+            }
+        }
+
+        let pct = personality.material;
+        for (phase, heatmap) in self.heatmap.iter_mut().enumerate() {
+            for (j, material) in PIECE_WEIGHTS[phase].iter().enumerate() {
+                let delta = material * (pct - 100) / 100;
+                for weight in heatmap[(j << 1) + 2].iter_mut() {
+                    *weight += delta;
+                }
+                for weight in heatmap[(j << 1) + 3].iter_mut() {
+                    *weight -= delta;
+                }
+            }
+        }
+        for name in ["g_atk_pro_pinned", "g_threat_king", "g_hanging", "g_threat_safe_pawn", "g_threat_push", "g_knight_on_queen", "g_slider_on_queen", "s_bishop_pair"] {
+            scale(self.scalar_mut(name).unwrap(), pct);
+        }
+        scale(self.g_threat_minor.as_flattened_mut(), pct);
+        scale(self.g_threat_rook.as_flattened_mut(), pct);
+        scale(self.m_imbalance_ours.as_flattened_mut(), pct);
+        scale(self.m_imbalance_theirs.as_flattened_mut(), pct);
+
+        let pct = personality.pawns;
+        for name in ["p_isolated", "p_doubled", "p_phalanga", "p_outpost_block", "p_semiblocked", "p_blocked", "p_backward", "p_passer_protected", "p_passer_connected", "g_ppawn_block"] {
+            scale(self.scalar_mut(name).unwrap(), pct);
+        }
+        for name in ["k_pawn_dist1", "k_pawn_dist2", "k_passer_dist", "p_passer_free", "p_unstoppable"] {
+            scale(self.phased_mut(name).unwrap().as_flattened_mut(), pct);
+        }
+        scale(self.p_passing.as_flattened_mut(), pct);
+        scale(self.p_candidate.as_flattened_mut(), pct);
+        for name in ["k_storm_blocked", "k_open_file"] {
+            scale(self.scalar_mut(name).unwrap(), pct);
+        }
+        scale(self.k_shelter.as_flattened_mut(), pct);
+        scale(self.k_storm.as_flattened_mut(), pct);
+
+        let pct = personality.positional;
+        for name in ["p_atk_center", "p_outpost", "nb_outpost", "nb_outpost_reach", "rq_open", "rq_semiopen", "rq_atk_open", "rq_atk_semiopen", "g_atk_ppt", "g_restricted"] {
+            scale(self.scalar_mut(name).unwrap(), pct);
+        }
+        for name in ["g_atk_center", "r_seventh", "r_behind_passer", "r_trapped", "b_bad", "b_trapped", "q_early", "s_space"] {
+            scale(self.phased_mut(name).unwrap().as_flattened_mut(), pct);
+        }
+
+        scale(self.k_danger.as_flattened_mut(), personality.king_attack);
+        scale(self.s_mob_knight.as_flattened_mut().as_flattened_mut(), personality.mobility);
+        scale(self.s_mob_bishop.as_flattened_mut().as_flattened_mut(), personality.mobility);
+        scale(self.s_mob_rook.as_flattened_mut().as_flattened_mut(), personality.mobility);
+        scale(self.s_mob_queen.as_flattened_mut().as_flattened_mut(), personality.mobility);
     }
 }
 
 fn colour_transform(weight: i32) -> [i32; 2] {
     [weight, -weight]
-}
\ No newline at end of file
+}
+
+// [phase][count] for white -> [phase][colour][count]
+fn mobility_transform<const N: usize>(weights: [[i32; N]; 2]) -> [[[i32; N]; 2]; 2] {
+    weights.map(|weights| [weights, weights.map(|weight| -weight)])
+}
+
+// per rank weights for white -> [colour][rank], black ranks are mirrored
+fn rank_transform(weights: [i32; 8]) -> [[i32; 8]; 2] {
+    let mut black = weights.map(|weight| -weight);
+    black.reverse();
+    [weights, black]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::personality::Personality;
+
+    #[test]
+    fn test_weights_load_and_personality() {
+        let path = std::env::temp_dir().join("ace_test_weights.txt");
+        fs::write(&path, "# custom\nk_opposition 0 60\ns_mob_knight 0 1 2 3 4 5 6 7 8 0 2 4 6 8 10 12 14 16\np_passing 0 1 2 3 4 5 6 0\n").unwrap();
+        let mut w = Weights::init();
+        w.load(path.to_str().unwrap()).unwrap();
+        assert_eq!(w.k_opposition, [[0, 0], [60, -60]]);
+        assert_eq!((w.s_mob_knight[0][0][3], w.s_mob_knight[1][1][8]), (3, -16));
+        assert_eq!((w.p_passing[0][6], w.p_passing[1][1]), (6, -6));
+
+        fs::write(&path, "no_such_weight 1\n").unwrap();
+        assert!(w.load(path.to_str().unwrap()).is_err());
+        fs::remove_file(&path).unwrap();
+
+        // the default personality changes nothing, others scale their groups
+        let base = Weights::init();
+        let mut w = Weights::init();
+        w.apply_personality(&Personality::default());
+        assert_eq!(w.heatmap, base.heatmap);
+        assert_eq!(w.k_danger, base.k_danger);
+        w.apply_personality(&Personality::preset("Aggressive").unwrap());
+        assert_eq!(w.k_danger[0][40], base.k_danger[0][40] * 3 / 2);
+        assert_eq!(w.k_danger[1][40], -w.k_danger[0][40]);
+        assert!(w.heatmap[0][10][27] < base.heatmap[0][10][27]);
+    }
+
+    #[test]
+    fn test_weights_imbalance() {
+        let mut w = Weights::init();
+        // the same material is no imbalance
+        let start = [0, 8, 2, 2, 2, 1];
+        let imbalance = w.imbalance(&[start, start]);
+        assert_eq!(imbalance[0], -imbalance[1]);
+
+        // a knight is worth more with more pawns, a rook isn't
+        let knight = |pawns: i32| {
+            let imbalance = w.imbalance(&[[0, pawns, 1, 0, 0, 0], [0, pawns, 0, 0, 0, 0]]);
+            imbalance[0] + imbalance[1]
+        };
+        assert!(knight(8) > knight(2));
+        let rook = |pawns: i32| {
+            let imbalance = w.imbalance(&[[0, pawns, 0, 0, 1, 0], [0, pawns, 0, 0, 0, 0]]);
+            imbalance[0] + imbalance[1]
+        };
+        assert!(rook(8) - rook(2) < knight(8) - knight(2));
+
+        // black's side is the mirror
+        let (white, black) = ([1, 6, 1, 2, 1, 0], [0, 5, 2, 0, 0, 1]);
+        let imbalance = w.imbalance(&[white, black]);
+        let mirrored = w.imbalance(&[black, white]);
+        assert_eq!((imbalance[0], imbalance[1]), (-mirrored[1], -mirrored[0]));
+
+        // tables are set by their lower triangle
+        assert!(w.set("m_imbalance_theirs", &(0..21).collect::<Vec<i32>>()));
+        assert_eq!((w.m_imbalance_theirs[1][1], w.m_imbalance_theirs[5][5], w.m_imbalance_theirs[1][2]), (2, 20, 0));
+        assert!(!w.set("m_imbalance_ours", &[1, 2, 3]));
+    }
+}
@@ -90,6 +90,47 @@ impl Zobrist {
         hash ^= self.hash_turn;
         hash
     }
+
+    // pawns only, a key for the pawn structure cache (a null move keeps it as is)
+    pub fn pawn_new(&self, board: &Board) -> u64 {
+        let mut hash = 0;
+        for (i, bb) in board.bbs.into_iter().enumerate().skip(P).take(2) {
+            let mut mask = bb;
+            while mask != 0 {
+                let csq = pop_bit(&mut mask);
+                hash ^= self.hash_boards[i][csq];
+            }
+        }
+        hash
+    }
+
+    // same as cache_iter(), but only pawns are hashed
+    pub fn pawn_iter(&self, board: &Board, last_move: u32, prev_hash: u64) -> u64 {
+        let mut hash = prev_hash;
+        let from  = move_get_from(last_move, !board.turn);
+        let to    = move_get_to(last_move, !board.turn);
+        let piece = move_get_piece(last_move);
+        let capt  = move_get_capture(last_move);
+        if piece < N {
+            hash ^= self.hash_boards[piece][from];
+            if move_get_promotion(last_move) == E {
+                hash ^= self.hash_boards[piece][to];
+            }
+        }
+        if capt != E && capt < N {
+            if last_move & MSE_EN_PASSANT != 0 {
+                hash ^= self.hash_boards[capt][to + !board.turn as usize * 16 - 8];
+            } else {
+                hash ^= self.hash_boards[capt][to];
+            }
+        }
+        hash
+    }
+
+    // same as cache_iter(), but for a null move (call it after board.make_null_move())
+    pub fn cache_null(&self, board: &Board, prev_hash: u64) -> u64 {
+        prev_hash ^ self.hash_turn ^ self.hash_en_passant[board.en_passant] ^ self.hash_en_passant[*board.enp_history.last().unwrap()]
+    }
 }
 
 
@@ -120,4 +161,41 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_zobrist_pawn_iter() {
+        let zob = Zobrist::default();
+        let positions = [
+            "r3k2r/pb1n1pbp/4qnp1/1Pppp3/P3P3/2Np1N2/1BP1QPPP/R3K2R w Kkq c6 0 15",
+            "r1b1k2r/1P3ppp/8/8/8/8/p4PPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pppp2pp/8/4pp1Q/4PP2/8/PPPP2PP/RNB1KBNR b KQkq - 1 3"
+        ];
+        for pos in positions.into_iter() {
+            let mut board = Board::import(pos);
+            let legals = board.get_legal_moves();
+            let prev_hash = zob.pawn_new(&board);
+            for legal in legals.into_iter() {
+                board.make_move(legal);
+                assert_eq!(zob.pawn_iter(&board, legal, prev_hash), zob.pawn_new(&board));
+                board.revert_move();
+            }
+        }
+    }
+
+    #[test]
+    fn test_zobrist_cache_null() {
+        let zob = Zobrist::default();
+        let positions = [
+            "rnbqkbnr/pppp2pp/8/4pp1Q/4PP2/8/PPPP2PP/RNB1KBNR b KQkq - 1 3",
+            "r3k2r/pbpn1pbp/3pqnp1/4p3/P1pPP3/1PN2N2/1BP1QPPP/R3K2R b Kkq d3 0 12"
+        ];
+        for pos in positions.into_iter() {
+            let mut board = Board::import(pos);
+            let prev_hash = zob.cache_new(&board);
+            board.make_null_move();
+            assert_eq!(zob.cache_null(&board, prev_hash), zob.cache_new(&board));
+            board.unmake_null_move();
+            assert_eq!(zob.cache_new(&board), prev_hash);
+        }
+    }
 }
\ No newline at end of file
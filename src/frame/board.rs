@@ -1,3 +1,4 @@
+use std::sync::Arc;
 use super::{util::*, maps::Maps};
 
 /* Bitboard index structure (Little-Endian):
@@ -12,6 +13,7 @@ use super::{util::*, maps::Maps};
     1 |  7  6  5  4  3  2  1  0     1 |  0  1  2  3  4  5  6  7
 */
 
+#[derive(Clone)]
 pub struct Board {
     pub bbs:          [u64; 14],    // bitboards (E, E|1, P - K2)
     pub turn:         bool,         // is black to move
@@ -21,6 +23,9 @@ pub struct Board {
     pub hmc:          u16,          // halfmove clock (which drops for every capture or pawn movement)
     pub no:           i16,          /* halfmove number
                                         it should act as a fullmove number in import/export (which increases after each black move) */
+    pub phase:        u32,          // phase counter (util.rs has weights), kept by make/revert
+    pub psqt:         [[i32; 2]; 2],// heatmap sums per [colour][phase], kept by make/revert if there's a heatmap
+    pub heatmap:      Option<Arc<Heatmap>>,
     /* Accessible constants */
     pub maps:         Maps,
     /* Takeback funcitonal */
@@ -111,18 +116,66 @@ impl Board {
         // fullmove to halfmove
         no = (no - 1) * 2 + turn as i16;
 
-        Self { 
+        let mut board = Self { 
             bbs, 
             turn,
             castlings,
             en_passant,
             hmc,
             no,
+            phase:        0,
+            psqt:         [[0; 2]; 2],
+            heatmap:      None,
             maps:         Maps::default(),
             move_history: Vec::with_capacity(300),
             hmc_history:  Vec::with_capacity(300),
             enp_history:  Vec::with_capacity(300),
             cst_history:  Vec::with_capacity(300)
+        };
+        board.phase = board.count_phase();
+        board
+    }
+
+    // attach the tables to be summed incrementally (None to stop), sums are recounted
+    pub fn set_heatmap(&mut self, heatmap: Option<Arc<Heatmap>>) {
+        self.heatmap = heatmap;
+        self.psqt = self.count_psqt();
+    }
+
+    // from scratch, make/revert keep the same value in self.phase
+    pub fn count_phase(&self) -> u32 {
+        self.bbs.iter().zip(PHASE_WEIGHTS.iter()).map(|(bb, weight)| bb.count_ones() * weight).sum()
+    }
+
+    // from scratch, make/revert keep the same value in self.psqt
+    pub fn count_psqt(&self) -> [[i32; 2]; 2] {
+        let mut psqt = [[0; 2]; 2];
+        if let Some(heatmap) = &self.heatmap {
+            for piece in P..=K2 {
+                let mut bb = self.bbs[piece];
+                while bb != 0 {
+                    let sq = pop_bit(&mut bb);
+                    psqt[piece & 1][0] += heatmap[0][piece][sq];
+                    psqt[piece & 1][1] += heatmap[1][piece][sq];
+                }
+            }
+        }
+        psqt
+    }
+
+    #[inline]
+    fn psqt_add(&mut self, piece: usize, sq: usize) {
+        if let Some(heatmap) = &self.heatmap {
+            self.psqt[piece & 1][0] += heatmap[0][piece][sq];
+            self.psqt[piece & 1][1] += heatmap[1][piece][sq];
+        }
+    }
+
+    #[inline]
+    fn psqt_del(&mut self, piece: usize, sq: usize) {
+        if let Some(heatmap) = &self.heatmap {
+            self.psqt[piece & 1][0] -= heatmap[0][piece][sq];
+            self.psqt[piece & 1][1] -= heatmap[1][piece][sq];
         }
     }
 
@@ -164,8 +217,13 @@ impl Board {
         let capt  = move_get_capture(mov);
 
         del_bit(&mut self.bbs[piece], from);
+        self.psqt_del(piece, from);
         if capt != E {
             del_bit(&mut self.bbs[capt], to);
+            self.phase -= PHASE_WEIGHTS[capt];
+            if mov & MSE_EN_PASSANT == 0 {
+                self.psqt_del(capt, to);
+            }
             self.hmc = 0;
             if capt | 1 == R | R2 {
                 match to {
@@ -179,22 +237,31 @@ impl Board {
         }
         if move_get_promotion(mov) != E {
             set_bit(&mut self.bbs[move_get_promotion(mov)], to);
+            self.psqt_add(move_get_promotion(mov), to);
+            self.phase += PHASE_WEIGHTS[move_get_promotion(mov)];
         } else {
             set_bit(&mut self.bbs[piece], to);
+            self.psqt_add(piece, to);
             if mov & MSE_EN_PASSANT != 0 {
                 if self.turn {
                     del_bit(&mut self.bbs[P ], to + 8);
+                    self.psqt_del(P, to + 8);
                 } else {
                     del_bit(&mut self.bbs[P2], to - 8);
+                    self.psqt_del(P2, to - 8);
                 }
                 self.hmc = 0;
             } else if piece == K | turn {
                 if mov & MSE_CASTLE_SHORT != 0 {
                     del_bit(&mut self.bbs[R | turn], to   | 1);
                     set_bit(&mut self.bbs[R | turn], from | 1);
+                    self.psqt_del(R | turn, to | 1);
+                    self.psqt_add(R | turn, from | 1);
                 } else if mov & MSE_CASTLE_LONG != 0 {
                     del_bit(&mut self.bbs[R | turn], to   - 2);
                     set_bit(&mut self.bbs[R | turn], to   | 1);
+                    self.psqt_del(R | turn, to - 2);
+                    self.psqt_add(R | turn, to | 1);
                 }
                 self.castlings &= !(CSW << turn);
                 self.castlings &= !(CLW << turn);
@@ -235,26 +302,53 @@ impl Board {
         let piece = move_get_piece(mov);
 
         set_bit(&mut self.bbs[piece], from);
+        self.psqt_add(piece, from);
         if move_get_promotion(mov) != E {
             del_bit(&mut self.bbs[move_get_promotion(mov)], to);
+            self.psqt_del(move_get_promotion(mov), to);
+            self.phase -= PHASE_WEIGHTS[move_get_promotion(mov)];
         } else {
             del_bit(&mut self.bbs[piece], to);
+            self.psqt_del(piece, to);
         }
         if move_get_capture(mov) != E {
-            if mov & MSE_EN_PASSANT != 0 {
-                set_bit(&mut self.bbs[move_get_capture(mov)], to + ((self.turn as usize) << 4) - 8);
-            } else {
-                set_bit(&mut self.bbs[move_get_capture(mov)], to);
-            }
+            let capt = move_get_capture(mov);
+            let csq = if mov & MSE_EN_PASSANT != 0 { to + ((self.turn as usize) << 4) - 8 } else { to };
+            set_bit(&mut self.bbs[capt], csq);
+            self.psqt_add(capt, csq);
+            self.phase += PHASE_WEIGHTS[capt];
         } else if mov & MSE_CASTLE_SHORT != 0 {
             del_bit(&mut self.bbs[R + self.turn as usize], from | 1);
             set_bit(&mut self.bbs[R + self.turn as usize], to   | 1);
+            self.psqt_del(R + self.turn as usize, from | 1);
+            self.psqt_add(R + self.turn as usize, to | 1);
         } else if mov & MSE_CASTLE_LONG != 0 {
             del_bit(&mut self.bbs[R + self.turn as usize], to   | 1);
             set_bit(&mut self.bbs[R + self.turn as usize], to   - 2);
+            self.psqt_del(R + self.turn as usize, to | 1);
+            self.psqt_add(R + self.turn as usize, to - 2);
         }
     }
 
+    /* Null move: pass the turn without moving anything.
+       It's stored as 0 in move_history, so other histories stay aligned with it. */
+    pub fn make_null_move(&mut self) {
+        self.move_history.push(0);
+        self.hmc_history.push(self.hmc);
+        self.enp_history.push(self.en_passant);
+        self.cst_history.push(self.castlings);
+        self.en_passant = 0;
+        self.turn = !self.turn;
+    }
+
+    pub fn unmake_null_move(&mut self) {
+        self.move_history.pop();
+        self.en_passant = self.enp_history.pop().unwrap();
+        self.hmc        = self.hmc_history.pop().unwrap();
+        self.castlings  = self.cst_history.pop().unwrap();
+        self.turn = !self.turn;
+    }
+
     pub fn get_pseudo_legal_moves(&self) -> Vec<u32> {
         let mut moves = Vec::with_capacity(64);
         let turn = self.turn as usize;
@@ -526,8 +620,8 @@ impl Board {
         }
         fen.push(' ');
         if self.en_passant != 0 {
-            fen.push(char::from_u32( self.en_passant as u32 / 8  + 'b' as u32).unwrap());
-            fen.push(char::from_u32((self.en_passant as u32 & 7) + '0' as u32).unwrap());
+            fen.push(char::from_u32((self.en_passant as u32 & 7) + 'a' as u32).unwrap());
+            fen.push(char::from_u32( self.en_passant as u32 / 8  + '1' as u32).unwrap());
         } else {
             fen.push('-');
         }
@@ -571,6 +665,65 @@ impl Board {
         self.maps.attacks_rook[magic_index as usize + self.maps.ais_rook[sq]]
     }
 
+    // anything except pawns and king (null move is unsafe without it because of zugzwang)
+    #[inline]
+    pub fn has_non_pawn_material(&self, turn: bool) -> bool {
+        let turn = turn as usize;
+        self.bbs[N | turn] | self.bbs[B | turn] | self.bbs[R | turn] | self.bbs[Q | turn] != 0
+    }
+
+    // nobody can mate: bare kings, a single minor piece or bishops on squares of one colour only
+    pub fn is_insufficient_material(&self) -> bool {
+        if self.bbs[P] | self.bbs[P2] | self.bbs[R] | self.bbs[R2] | self.bbs[Q] | self.bbs[Q2] != 0 {
+            return false;
+        }
+        let knights = self.bbs[N] | self.bbs[N2];
+        let bishops = self.bbs[B] | self.bbs[B2];
+        if (knights | bishops).count_ones() < 2 {
+            return true;
+        }
+        knights == 0 && (bishops & LIGHT_SQUARES == 0 || bishops & !LIGHT_SQUARES == 0)
+    }
+
+    // the same position with colours swapped (the board is mirrored vertically), history is not kept
+    pub fn color_flipped(&self) -> Self {
+        let mut bbs = [0; 14];
+        for (piece, bb) in self.bbs.iter().enumerate() {
+            let mut mask = *bb;
+            while mask != 0 {
+                let sq = pop_bit(&mut mask);
+                set_bit(&mut bbs[piece ^ 1], flip(sq));
+            }
+        }
+        let mut castlings = 0;
+        for (white, black) in [(CSW, CSB), (CLW, CLB)] {
+            if self.castlings & white != 0 {
+                castlings |= black;
+            }
+            if self.castlings & black != 0 {
+                castlings |= white;
+            }
+        }
+        let mut board = Self {
+            bbs,
+            turn:         !self.turn,
+            castlings,
+            en_passant:   if self.en_passant != 0 { flip(self.en_passant) } else { 0 },
+            hmc:          self.hmc,
+            no:           self.no ^ 1,
+            phase:        self.phase,
+            psqt:         [[0; 2]; 2],
+            heatmap:      None,
+            maps:         self.maps.clone(),
+            move_history: Vec::with_capacity(300),
+            hmc_history:  Vec::with_capacity(300),
+            enp_history:  Vec::with_capacity(300),
+            cst_history:  Vec::with_capacity(300)
+        };
+        board.set_heatmap(self.heatmap.clone());
+        board
+    }
+
     // although it's unused by the board itself
     pub fn is_in_check(&self) -> bool {
         let ally = self.get_occupancies(self.turn);
@@ -664,6 +817,99 @@ mod tests {
         assert_eq!(board1.export(), board3.export());
     }
 
+    #[test]
+    fn test_board_null_move() {
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "1rbq1r1k/p1ppB1pp/2p5/8/2BPp1n1/2N4N/P1P1Q1PP/R3K2R b KQ d3 0 15",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1"
+        ];
+        for fen in fens.into_iter() {
+            let mut board = Board::import(fen);
+            let turn = board.turn;
+            board.make_null_move();
+            assert_eq!(board.turn, !turn);
+            assert_eq!(board.en_passant, 0);
+            assert_eq!(*board.move_history.last().unwrap(), 0);
+            board.unmake_null_move();
+            assert_eq!(board.export(), fen);
+            assert!(board.move_history.is_empty());
+        }
+        let board = Board::import("4k3/pppp4/8/8/8/8/PPPP4/4K2N w - - 0 1");
+        assert!( board.has_non_pawn_material(false));
+        assert!(!board.has_non_pawn_material(true));
+    }
+
+    #[test]
+    fn test_board_insufficient_material() {
+        let draws = [
+            "8/8/4k3/8/8/3K4/8/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/5B2 w - - 0 1",
+            "8/8/4k3/8/2n5/3K4/8/8 b - - 0 1",
+            "8/8/4k3/8/2b5/3K4/8/5B2 w - - 0 1",
+            "2b5/8/4k3/8/8/3K4/B7/5B2 w - - 0 1"
+        ];
+        for fen in draws.into_iter() {
+            assert!(Board::import(fen).is_insufficient_material(), "{}", fen);
+        }
+        let not_draws = [
+            "8/8/4k3/8/2b5/3K4/8/4B3 w - - 0 1",
+            "8/8/4k3/8/2n5/3K4/8/5B2 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/4NN2 w - - 0 1",
+            "8/8/4k3/8/8/3K4/7P/8 w - - 0 1",
+            "8/8/4k3/8/8/3K4/8/7r w - - 0 1"
+        ];
+        for fen in not_draws.into_iter() {
+            assert!(!Board::import(fen).is_insufficient_material(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn test_board_color_flipped() {
+        let board = Board::import("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K1R1 w Qkq - 3 12");
+        let flipped = board.color_flipped();
+        assert_eq!(flipped.export(), "r3k1r1/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b KQq - 3 12");
+        assert_eq!(flipped.color_flipped().export(), board.export());
+
+        let mut board = Board::import("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3");
+        let mut flipped = board.color_flipped();
+        assert_eq!(flipped.en_passant, flip(board.en_passant));
+        assert_eq!(flipped.get_legal_moves().len(), board.get_legal_moves().len());
+    }
+
+    #[test]
+    fn test_board_incremental_psqt() {
+        // every node of a shallow tree, with castlings, en passant, promotions and captures of them
+        fn walk(board: &mut Board, depth: usize) {
+            assert_eq!(board.phase, board.count_phase());
+            assert_eq!(board.psqt, board.count_psqt());
+            if depth == 0 {
+                return;
+            }
+            for mov in board.get_legal_moves() {
+                board.make_move(mov);
+                walk(board, depth - 1);
+                board.revert_move();
+            }
+        }
+        let mut heatmap: Heatmap = [[[0; 64]; 14]; 2];
+        for (phase, pieces) in heatmap.iter_mut().enumerate() {
+            for (piece, squares) in pieces.iter_mut().enumerate() {
+                for (sq, weight) in squares.iter_mut().enumerate() {
+                    *weight = (piece * 64 + sq + phase) as i32 * if piece & 1 == 0 { 1 } else { -3 };
+                }
+            }
+        }
+        let heatmap = Arc::new(heatmap);
+        for fen in ["r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "n1n5/PPPk4/8/8/8/8/4Kppp/5N1N b - - 0 1", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1"] {
+            let mut board = Board::import(fen);
+            board.set_heatmap(Some(heatmap.clone()));
+            walk(&mut board, 3);
+            assert_eq!(board.color_flipped().psqt, board.color_flipped().count_psqt());
+        }
+        assert_eq!(Board::default().phase, 56);
+    }
+
     #[test]
     fn test_board_magic() {
         let mut board = Board::default();
@@ -1,5 +1,6 @@
 use super::util::*;
 
+#[derive(Clone)]
 pub struct Maps {
     pub attacks_rook:        Vec<u64>,
     pub ais_rook:           [usize; 64],
@@ -3,7 +3,7 @@
 
 #![allow(dead_code)]
 
-use std::{cmp::min, fs, io::Cursor, path::Path};
+use std::{cmp::{max, min}, fs, io::Cursor, path::Path};
 use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
 use phf::phf_map;
 
@@ -15,6 +15,12 @@ pub const CACHE_SIZE: usize = 25;  // entries, in power of 2;
                                    // keep in mind, programm will eat additional 1.4 MB bc of lookup tables.
                                    // 25 is recommended (it's 512 MB)
 pub const TEMP_PRE_CALC_CACHE_BITMASK: u64 = (1 << CACHE_SIZE) - 1; // sorry for that
+pub const PAWN_CACHE_SIZE: usize = 16;                              // pawn structure entries, in power of 2 (7 MB)
+pub const PAWN_CACHE_BITMASK: u64 = (1 << PAWN_CACHE_SIZE) - 1;
+pub const MATERIAL_CACHE_SIZE: usize = 12;                          // material imbalance entries, in power of 2
+pub const MATERIAL_CACHE_BITMASK: u64 = (1 << MATERIAL_CACHE_SIZE) - 1;
+pub const STATIC_CACHE_SIZE: usize = 18;                            // static eval entries, in power of 2 (4 MB)
+pub const STATIC_CACHE_BITMASK: u64 = (1 << STATIC_CACHE_SIZE) - 1;
 
 pub const HALF_DEPTH_LIMIT: usize = 64;
 pub const HALF_DEPTH_LIMIT_SAFE: i16 = 50;                                      // for chara.think()
@@ -87,6 +93,8 @@ pub const FILE_F: u64 = 0x2020202020202020;
 pub const FILE_G: u64 = 0x4040404040404040;
 pub const FILE_H: u64 = 0x8080808080808080;
 
+pub const LIGHT_SQUARES: u64 = 0x55AA55AA55AA55AA;
+
 pub const CSMASK: u64 = 0x0000000000000060;
 pub const CLMASK: u64 = 0x000000000000000E;
 
@@ -115,6 +123,12 @@ pub const CSB: u8 = 0b0010; // castle short black
 pub const CLW: u8 = 0b0100; // castle long white
 pub const CLB: u8 = 0b1000; // castle long black
 
+// phase counter weights per piece, 56 is the full board
+pub const PHASE_WEIGHTS: [u32; 14] = [0, 0, 0, 0, 3, 3, 3, 3, 4, 4, 8, 8, 0, 0];
+
+// piece values and squares per [phase][piece][square], colour-signed
+pub type Heatmap = [[[i32; 64]; 14]; 2];
+
 pub const LARGE: i32 = 0x00100000;
 pub const INF:   i32 = 0x01000000;
 pub const LARGM: i32 = LARGE - (HALF_DEPTH_LIMIT << 1) as i32;
@@ -165,6 +179,12 @@ pub fn glz(bitboard: u64) -> usize {
     63 - u64::leading_zeros(bitboard) as usize
 }
 
+// king moves from one square to another
+#[inline]
+pub fn distance(sq1: usize, sq2: usize) -> usize {
+    max((sq1 & 7).abs_diff(sq2 & 7), (sq1 >> 3).abs_diff(sq2 >> 3))
+}
+
 // return trailing zeros, then remove last bit
 #[inline]
 pub fn pop_bit(bitboard: &mut u64) -> usize {
@@ -252,7 +272,7 @@ impl EvalMove {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Default)]
 pub struct EvalHash {
     pub hash: u64,
     pub score: i32, 
@@ -272,15 +292,31 @@ impl EvalHash {
     }
 }
 
-impl Default for EvalHash {
-    fn default() -> Self {
-        Self {
-            hash: 0,
-            score: 0,
-            depth: 0,
-            flag: 0
-        }
-    }
+// pawn structure terms (white's view) and the maps the piece terms are built on
+#[derive(Copy, Clone, Default)]
+pub struct PawnHash {
+    pub hash: u64,
+    pub score: i32,                 // the same for both phases
+    pub score_pd: [i32; 2],         // opening, endgame
+    pub pattacks: [u64; 2],
+    pub pass: [u64; 2],             // passed pawns
+    pub ppt: [u64; 2],              // paths of passed pawns
+    pub sof: [u64; 2],              // files that are not open for the colour
+    pub outposts: [u64; 2]
+}
+
+// material imbalance (colour-signed) by the material key
+#[derive(Copy, Clone, Default)]
+pub struct MaterialHash {
+    pub hash: u64,
+    pub score: [i32; 2]
+}
+
+// static eval (side to move) by the position hash
+#[derive(Copy, Clone, Default)]
+pub struct StaticHash {
+    pub hash: u64,
+    pub score: i32
 }
 
 /* GENERAL FUNCTIONS */
@@ -467,6 +503,18 @@ pub fn move_transform(mov: u32, turn: bool) -> String {
     str
 }
 
+// EPD (or FEN) line -> FEN, None for empty lines and comments
+pub fn epd_to_fen(line: &str) -> Option<String> {
+    let fields = line.split_whitespace().collect::<Vec<&str>>();
+    if fields.len() < 4 || fields[0].starts_with('#') {
+        return None;
+    }
+    if fields.len() >= 6 && fields[4].parse::<u16>().is_ok() && fields[5].parse::<u16>().is_ok() {
+        return Some(fields[..6].join(" "));
+    }
+    Some(format!("{} 0 1", fields[..4].join(" ")))
+}
+
 // gui -> Option<engine>, if null - it's illegal
 pub fn move_transform_back(input: &str, legal_moves: &[u32], turn: bool) -> Option<u32> {
     let command     = input.as_bytes();
@@ -553,8 +601,8 @@ mod tests {
         const PATH: &str = "./TEST_FILE_1";
         let mut arr = [1, 2, 3, 4];
         for _ in 0..20 {
-            for j in 0..4 {
-                arr[j] = xor64(arr[j]);
+            for elem in arr.iter_mut() {
+                *elem = xor64(*elem);
             }
         }
         arr[2] = 1;
@@ -623,18 +671,18 @@ mod tests {
         let mut board = Board::default();
         let moves = board.get_legal_moves();
         let mov = move_transform_back("e2e4", &moves, board.turn);
-        assert_ne!(mov.is_none(), true);
+        assert!(mov.is_some());
         let mov = move_transform_back("e2e4q", &moves, board.turn);
-        assert_eq!(mov.is_none(), true);
+        assert!(mov.is_none());
         let mov = move_transform_back("e2e5", &moves, board.turn);
-        assert_eq!(mov.is_none(), true);
+        assert!(mov.is_none());
         let mut board = Board::import("r1bq1bnr/ppp1kP1p/2np2p1/4p3/8/3B1N2/PPPP1PPP/RNBQK2R w KQ - 1 7");
         let moves = board.get_legal_moves();
         let mov = move_transform_back("f7g8n", &moves, board.turn);
-        assert_ne!(mov.is_none(), true);
+        assert!(mov.is_some());
         let mov = move_transform_back("f7f8r", &moves, board.turn);
-        assert_eq!(mov.is_none(), true);
+        assert!(mov.is_none());
         let mov = move_transform_back("e1g1", &moves, board.turn);
-        assert_ne!(mov.is_none(), true);
+        assert!(mov.is_some());
     }
 }
\ No newline at end of file
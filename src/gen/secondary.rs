@@ -12,52 +12,52 @@ pub fn init_secondary_maps() {
 }
 
 fn init_ranks(ranks: &mut[u64]) {
-    for i in 0..64 {
+    for (i, rank) in ranks.iter_mut().enumerate() {
         let offset = i & 56;
         for j in 0..8 {
-            set_bit(&mut ranks[i], j + offset);
+            set_bit(rank, j + offset);
         }
-        del_bit(&mut ranks[i], i);
+        del_bit(rank, i);
     }
 }
 
 fn init_files(files: &mut[u64]) {
-    for i in 0..64 {
+    for (i, file) in files.iter_mut().enumerate() {
         let mut j = i & 7;
         while j < 64 {
-            set_bit(&mut files[i], j);
+            set_bit(file, j);
             j += 8;
         }
-        del_bit(&mut files[i], i);
+        del_bit(file, i);
     }
 }
 
 fn init_flanks(map: &mut[u64]) {
-    for i in 0..64 {
+    for (i, flank) in map.iter_mut().enumerate() {
         let file = i & 7;
         for j in (file..64).step_by(8) {
             if file != 0 {
-                set_bit(&mut map[i], j - 1);
+                set_bit(flank, j - 1);
             }
             if file != 7 {
-                set_bit(&mut map[i], j + 1);
+                set_bit(flank, j + 1);
             }
         }
     }
 }
 
 fn init_forward_field_white(map: &mut[u64]) {
-    for i in 0..56 {
+    for (i, field) in map.iter_mut().enumerate().take(56) {
         for j in ((i & 56) + 8)..64 {
-            set_bit(&mut map[i], j);
+            set_bit(field, j);
         }
     }
 }
 
 fn init_forward_field_black(map: &mut[u64]) {
-    for i in 8..64 {
+    for (i, field) in map.iter_mut().enumerate().skip(8) {
         for j in 0..(i & 56) {
-            set_bit(&mut map[i], j);
+            set_bit(field, j);
         }
     }
 }
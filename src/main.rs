@@ -3,15 +3,36 @@ mod frame;
 mod engine;
 
 use std::time::Duration;
-use std::{io, thread};
+use std::{env, io, thread};
 use std::sync::mpsc::channel;
 use crate::gen::{leaping::init_leaping_attacks, magic::init_magics, secondary::init_secondary_maps};
-use crate::engine::chara::Chara;
+use crate::engine::{chara::Chara, solver::Solver};
+use crate::frame::{board::Board, util::epd_to_fen};
 
 fn main() {
     init_magics(&mut 1773); // good random number!
     init_leaping_attacks();
     init_secondary_maps();
+
+    // ace mate N FEN [checks] - solve a chess problem and exit
+    let args = env::args().skip(1).collect::<Vec<String>>();
+    if args.len() > 2 && args[0] == "mate" {
+        let mut solver = Solver::default();
+        let mut fen = args[2..].join(" ");
+        if let Some(stripped) = fen.strip_suffix(" checks") {
+            solver.checks_only = true;
+            fen = stripped.to_string();
+        }
+        match (args[1].parse::<usize>(), epd_to_fen(&fen)) {
+            (Ok(limit), Some(fen)) if limit > 0 => {
+                let mut board = Board::import(&fen);
+                let report = solver.solve(&mut board, limit, &mut || false);
+                report.print(limit, board.turn, solver.checks_only);
+            },
+            _ => println!("Usage: ace mate N FEN [checks]")
+        }
+        return;
+    }
     
     let (tx, rx) = channel();
     let mut chara = Chara::init("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", rx);